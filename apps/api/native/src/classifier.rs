@@ -0,0 +1,384 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tokio::task;
+use url::Url;
+
+use crate::crawler::LinkHint;
+
+#[derive(Deserialize, Default)]
+#[napi(object)]
+pub struct ClassifyLinksCall {
+  pub links: Vec<String>,
+  /// Base for resolving relative links.
+  pub base_url: Option<String>,
+  /// Hints keyed by link, as given in `links`.
+  pub link_hints: Option<HashMap<String, LinkHint>>,
+  /// Content types already seen for absolute URLs, e.g. from earlier responses.
+  pub content_types: Option<HashMap<String, String>>,
+}
+
+#[derive(Serialize)]
+#[napi(object)]
+pub struct LinkClassification {
+  pub link: String,
+  /// The resolved URL, if the link could be resolved.
+  pub url: Option<String>,
+  /// `page`, `document`, `image`, `media`, `archive`, `feed`, `sitemap` or `unknown`.
+  pub category: String,
+  /// What decided the category: `content_type`, `type_attribute`, `download_attribute`,
+  /// `extension`, `path`, `query`, `scheme`, `parse_error` or `default`.
+  pub signal: String,
+}
+
+#[derive(Serialize)]
+#[napi(object)]
+pub struct ClassifyLinksResult {
+  pub links: Vec<LinkClassification>,
+}
+
+const CATEGORY_PAGE: &str = "page";
+const CATEGORY_DOCUMENT: &str = "document";
+const CATEGORY_IMAGE: &str = "image";
+const CATEGORY_MEDIA: &str = "media";
+const CATEGORY_ARCHIVE: &str = "archive";
+const CATEGORY_FEED: &str = "feed";
+const CATEGORY_SITEMAP: &str = "sitemap";
+const CATEGORY_UNKNOWN: &str = "unknown";
+
+static LINK_CATEGORIES: &[&str] = &[
+  CATEGORY_PAGE,
+  CATEGORY_DOCUMENT,
+  CATEGORY_IMAGE,
+  CATEGORY_MEDIA,
+  CATEGORY_ARCHIVE,
+  CATEGORY_FEED,
+  CATEGORY_SITEMAP,
+  CATEGORY_UNKNOWN,
+];
+
+const SIGNAL_CONTENT_TYPE: &str = "content_type";
+const SIGNAL_TYPE_ATTRIBUTE: &str = "type_attribute";
+const SIGNAL_DOWNLOAD_ATTRIBUTE: &str = "download_attribute";
+const SIGNAL_EXTENSION: &str = "extension";
+const SIGNAL_PATH: &str = "path";
+const SIGNAL_QUERY: &str = "query";
+const SIGNAL_SCHEME: &str = "scheme";
+const SIGNAL_PARSE_ERROR: &str = "parse_error";
+const SIGNAL_DEFAULT: &str = "default";
+
+/// Query parameters that name a format (`?format=pdf`) or a filename (`?file=a.pdf`).
+static FORMAT_QUERY_PARAMS: &[&str] = &["format", "type", "filetype", "ext", "output"];
+static FILENAME_QUERY_PARAMS: &[&str] = &["filename", "file", "name", "attachment"];
+static DOWNLOAD_QUERY_PARAMS: &[&str] = &["download", "dl"];
+
+fn category_for_extension(extension: &str) -> Option<&'static str> {
+  let category = match extension {
+    "html" | "htm" | "xhtml" | "shtml" | "php" | "asp" | "aspx" | "jsp" | "cfm" => CATEGORY_PAGE,
+    "pdf" | "doc" | "docx" | "xls" | "xlsx" | "ppt" | "pptx" | "odt" | "ods" | "odp" | "rtf"
+    | "txt" | "csv" | "epub" => CATEGORY_DOCUMENT,
+    "png" | "jpg" | "jpeg" | "gif" | "svg" | "webp" | "ico" | "tif" | "tiff" | "bmp" | "avif"
+    | "heic" => CATEGORY_IMAGE,
+    "mp4" | "mp3" | "wav" | "avi" | "flv" | "mov" | "mkv" | "webm" | "ogg" | "m4a" | "aac"
+    | "flac" | "wmv" => CATEGORY_MEDIA,
+    "zip" | "tar" | "gz" | "tgz" | "rar" | "7z" | "bz2" | "xz" | "dmg" | "exe" | "iso" | "pkg"
+    | "msi" | "deb" | "rpm" | "apk" => CATEGORY_ARCHIVE,
+    "rss" | "atom" => CATEGORY_FEED,
+    "css" | "js" | "mjs" | "woff" | "woff2" | "ttf" | "otf" | "eot" | "inc" | "map" | "xml"
+    | "json" => CATEGORY_UNKNOWN,
+    _ => return None,
+  };
+  Some(category)
+}
+
+fn category_for_content_type(content_type: &str) -> Option<&'static str> {
+  let content_type = content_type
+    .split(';')
+    .next()
+    .unwrap_or("")
+    .trim()
+    .to_lowercase();
+  let category = match content_type.as_str() {
+    "text/html" | "application/xhtml+xml" => CATEGORY_PAGE,
+    "application/rss+xml" | "application/atom+xml" | "application/feed+json" => CATEGORY_FEED,
+    "application/pdf"
+    | "application/msword"
+    | "application/rtf"
+    | "application/epub+zip"
+    | "text/plain"
+    | "text/csv" => CATEGORY_DOCUMENT,
+    "application/zip"
+    | "application/gzip"
+    | "application/x-gzip"
+    | "application/x-tar"
+    | "application/x-7z-compressed"
+    | "application/x-rar-compressed"
+    | "application/vnd.rar"
+    | "application/x-bzip2" => CATEGORY_ARCHIVE,
+    ct if ct.starts_with("image/") => CATEGORY_IMAGE,
+    ct if ct.starts_with("audio/") || ct.starts_with("video/") => CATEGORY_MEDIA,
+    ct if ct.starts_with("application/vnd.openxmlformats-officedocument.")
+      || ct.starts_with("application/vnd.ms-")
+      || ct.starts_with("application/vnd.oasis.opendocument.") =>
+    {
+      CATEGORY_DOCUMENT
+    }
+    _ => return None,
+  };
+  Some(category)
+}
+
+fn file_extension(name: &str) -> Option<String> {
+  name
+    .rsplit_once('.')
+    .map(|(_, extension)| extension.to_lowercase())
+    .filter(|extension| !extension.is_empty())
+}
+
+/// Categorizes links from, in order of trust: known content types, the `<a>` element's `type`
+/// and `download` attributes, the last path segment, and query parameters.
+pub(crate) struct LinkClassifier {
+  content_types: HashMap<String, String>,
+}
+
+impl LinkClassifier {
+  pub(crate) fn new(content_types: Option<HashMap<String, String>>) -> Self {
+    Self {
+      content_types: content_types.unwrap_or_default(),
+    }
+  }
+
+  pub(crate) fn classify(
+    &self,
+    url: &Url,
+    hint: Option<&LinkHint>,
+  ) -> (&'static str, &'static str) {
+    if !matches!(url.scheme(), "http" | "https") {
+      return (CATEGORY_UNKNOWN, SIGNAL_SCHEME);
+    }
+
+    if let Some(category) = self
+      .content_types
+      .get(url.as_str())
+      .and_then(|content_type| category_for_content_type(content_type))
+    {
+      return (category, SIGNAL_CONTENT_TYPE);
+    }
+
+    let segment = url
+      .path_segments()
+      .and_then(|mut segments| segments.rfind(|s| !s.is_empty()))
+      .unwrap_or("")
+      .to_lowercase();
+    let path_category = Self::classify_segment(&segment);
+
+    if let Some(hint) = hint {
+      if let Some(category) = hint
+        .mime_type
+        .as_deref()
+        .and_then(category_for_content_type)
+      {
+        return (category, SIGNAL_TYPE_ATTRIBUTE);
+      }
+
+      if let Some(filename) = &hint.download {
+        let category = file_extension(filename)
+          .and_then(|extension| category_for_extension(&extension))
+          .or(path_category.map(|(category, _)| category))
+          .filter(|category| *category != CATEGORY_PAGE)
+          .unwrap_or(CATEGORY_DOCUMENT);
+        return (category, SIGNAL_DOWNLOAD_ATTRIBUTE);
+      }
+    }
+
+    if let Some(classification) = path_category {
+      return classification;
+    }
+
+    for (name, value) in url.query_pairs() {
+      let name = name.to_lowercase();
+      let category = if FORMAT_QUERY_PARAMS.contains(&name.as_str()) {
+        category_for_extension(&value.to_lowercase())
+      } else if FILENAME_QUERY_PARAMS.contains(&name.as_str()) {
+        file_extension(&value).and_then(|extension| category_for_extension(&extension))
+      } else if DOWNLOAD_QUERY_PARAMS.contains(&name.as_str()) && value != "0" && value != "false" {
+        Some(CATEGORY_DOCUMENT)
+      } else {
+        None
+      };
+      if let Some(category) = category.filter(|category| *category != CATEGORY_PAGE) {
+        return (category, SIGNAL_QUERY);
+      }
+    }
+
+    (CATEGORY_PAGE, SIGNAL_DEFAULT)
+  }
+
+  /// Classifies the last non-empty path segment. Only the segment's own extension counts, so
+  /// dotted directories like `/v1.2/docs` stay pages.
+  fn classify_segment(segment: &str) -> Option<(&'static str, &'static str)> {
+    if segment.contains("sitemap")
+      && [".xml", ".xml.gz", ".txt"]
+        .iter()
+        .any(|suffix| segment.ends_with(suffix))
+    {
+      return Some((CATEGORY_SITEMAP, SIGNAL_PATH));
+    }
+
+    if matches!(segment, "feed" | "rss" | "atom") {
+      return Some((CATEGORY_FEED, SIGNAL_PATH));
+    }
+
+    // Download endpoints like `/download?id=3` serve files under an extensionless path.
+    if matches!(segment, "download" | "attachment") {
+      return Some((CATEGORY_DOCUMENT, SIGNAL_PATH));
+    }
+
+    file_extension(segment)
+      .and_then(|extension| category_for_extension(&extension))
+      .map(|category| (category, SIGNAL_EXTENSION))
+  }
+}
+
+/// Checks `categories` against the known link categories.
+pub(crate) fn parse_link_categories(
+  categories: &[String],
+) -> std::result::Result<HashSet<&'static str>, String> {
+  categories
+    .iter()
+    .map(|category| {
+      LINK_CATEGORIES
+        .iter()
+        .copied()
+        .find(|known| known.eq_ignore_ascii_case(category.trim()))
+        .ok_or_else(|| format!("Unknown link category '{category}'"))
+    })
+    .collect()
+}
+
+fn _classify_links(data: ClassifyLinksCall) -> std::result::Result<ClassifyLinksResult, String> {
+  let base_url = data
+    .base_url
+    .as_deref()
+    .map(Url::parse)
+    .transpose()
+    .map_err(|e| format!("Base URL parse error: {e}"))?;
+  let classifier = LinkClassifier::new(data.content_types);
+  let link_hints = data.link_hints.unwrap_or_default();
+
+  let links = data
+    .links
+    .into_iter()
+    .map(|link| {
+      let resolved = match &base_url {
+        Some(base_url) => base_url.join(&link),
+        None => Url::parse(&link),
+      };
+      let (url, (category, signal)) = match resolved {
+        Ok(url) => {
+          let classification = classifier.classify(&url, link_hints.get(&link));
+          (Some(url.to_string()), classification)
+        }
+        Err(_) => (None, (CATEGORY_UNKNOWN, SIGNAL_PARSE_ERROR)),
+      };
+      LinkClassification {
+        link,
+        url,
+        category: category.to_string(),
+        signal: signal.to_string(),
+      }
+    })
+    .collect();
+
+  Ok(ClassifyLinksResult { links })
+}
+
+/// Categorize links as pages, documents, images, media, archives, feeds or sitemaps.
+#[napi]
+pub async fn classify_links(data: ClassifyLinksCall) -> Result<ClassifyLinksResult> {
+  let res = task::spawn_blocking(move || _classify_links(data))
+    .await
+    .map_err(|e| {
+      napi::Error::new(
+        napi::Status::GenericFailure,
+        format!("classify_links join error: {e}"),
+      )
+    })?;
+
+  res.map_err(|e| Error::new(Status::GenericFailure, format!("Classify links error: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_classify_links() {
+    let result = _classify_links(ClassifyLinksCall {
+      links: vec![
+        "/v1.2/docs".to_string(),
+        "/download?id=3".to_string(),
+        "/export?format=pdf".to_string(),
+        "/files/report.PDF".to_string(),
+        "/sitemap-posts.xml".to_string(),
+        "/blog/feed/".to_string(),
+        "/media/clip".to_string(),
+        "/get/12".to_string(),
+        "/styles/site.css".to_string(),
+        "/cached".to_string(),
+        "mailto:hi@example.com".to_string(),
+      ],
+      base_url: Some("https://example.com".to_string()),
+      link_hints: Some(HashMap::from([
+        (
+          "/media/clip".to_string(),
+          LinkHint {
+            mime_type: Some("video/mp4".to_string()),
+            download: None,
+            ..Default::default()
+          },
+        ),
+        (
+          "/get/12".to_string(),
+          LinkHint {
+            mime_type: None,
+            download: Some("slides.pptx".to_string()),
+            ..Default::default()
+          },
+        ),
+      ])),
+      content_types: Some(HashMap::from([(
+        "https://example.com/cached".to_string(),
+        "image/png; charset=binary".to_string(),
+      )])),
+    })
+    .unwrap();
+
+    let categories: Vec<(&str, &str)> = result
+      .links
+      .iter()
+      .map(|l| (l.category.as_str(), l.signal.as_str()))
+      .collect();
+    assert_eq!(
+      categories,
+      vec![
+        ("page", "default"),
+        ("document", "path"),
+        ("document", "query"),
+        ("document", "extension"),
+        ("sitemap", "path"),
+        ("feed", "path"),
+        ("media", "type_attribute"),
+        ("document", "download_attribute"),
+        ("unknown", "extension"),
+        ("image", "content_type"),
+        ("unknown", "scheme"),
+      ]
+    );
+    assert_eq!(
+      result.links[0].url.as_deref(),
+      Some("https://example.com/v1.2/docs")
+    );
+  }
+}
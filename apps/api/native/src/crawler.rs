@@ -1,15 +1,26 @@
+use chrono::{DateTime, NaiveDate, NaiveDateTime, Utc};
+use flate2::read::MultiGzDecoder;
 use napi::bindgen_prelude::*;
 use napi_derive::napi;
+use quick_xml::{events::Event, Reader};
 use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::{
   collections::{HashMap, HashSet},
-  sync::LazyLock,
+  io::{self, BufRead, BufReader, Read},
+  sync::{Arc, LazyLock},
 };
 use texting_robots::Robot;
 use tokio::task;
 use url::Url;
 
+use crate::classifier::{parse_link_categories, LinkClassifier};
+use crate::normalizer::{UrlNormalizationOptions, UrlNormalizer};
+use crate::robots::{
+  build_robot, robots_path, RobotsGroupMatch, RobotsRule, DEFAULT_ROBOTS_USER_AGENTS,
+};
+use crate::scoring::{LinkScorer, LinkScoringOptions};
+
 static FILE_EXTENSIONS: &[&str] = &[
   ".png", ".jpg", ".jpeg", ".gif", ".css", ".js", ".ico", ".svg", ".tiff", ".zip", ".exe", ".dmg",
   ".mp4", ".mp3", ".wav", ".pptx", ".xlsx", ".avi", ".flv", ".woff", ".ttf", ".woff2", ".webp",
@@ -19,22 +30,25 @@ static FILE_EXTENSIONS: &[&str] = &[
 static FILE_EXT_SET: LazyLock<HashSet<&'static str>> =
   LazyLock::new(|| FILE_EXTENSIONS.iter().copied().collect());
 
-#[derive(Deserialize)]
+static SOCIAL_MEDIA_DOMAINS: &[&str] = &[
+  "facebook.com",
+  "twitter.com",
+  "linkedin.com",
+  "instagram.com",
+  "pinterest.com",
+  "github.com",
+  "calendly.com",
+  "discord.gg",
+  "discord.com",
+];
+
+#[derive(Deserialize, Default)]
 #[napi(object)]
 pub struct FilterLinksCall {
   pub links: Vec<String>,
   pub limit: Option<i64>,
-  pub max_depth: u32,
-  pub base_url: String,
-  pub initial_url: String,
-  pub regex_on_full_url: bool,
-  pub excludes: Vec<String>,
-  pub includes: Vec<String>,
-  pub allow_backward_crawling: bool,
-  pub ignore_robots_txt: bool,
-  pub robots_txt: String,
-  pub allow_external_content_links: bool,
-  pub allow_subdomains: bool,
+  pub options: CrawlFilterOptions,
+  pub page: Option<FilterLinksPage>,
 }
 
 #[derive(Serialize)]
@@ -42,39 +56,279 @@ pub struct FilterLinksCall {
 pub struct FilterLinksResult {
   pub links: Vec<String>,
   pub denial_reasons: HashMap<String, String>,
+  /// The same denials as `denial_reasons`, with the rule that matched.
+  pub denials: HashMap<String, LinkDenial>,
+  /// robots.txt group that was applied: one of `robots_user_agents`, or `*`.
+  pub robots_user_agent: Option<String>,
+  /// Hop distance from the start URL of the returned links, `parent_depth + 1`, when
+  /// `parent_depth` was given. All links of a page share it.
+  pub link_depth: Option<u32>,
+  /// Score of each returned link, when `scoring` was given. Links are sorted by it.
+  pub scores: Option<HashMap<String, f64>>,
+}
+
+/// What is known about the page the links were found on.
+#[derive(Deserialize, Default)]
+#[napi(object)]
+pub struct FilterLinksPage {
+  /// `<a>` attribute hints keyed by link, used to classify links.
+  pub link_hints: Option<HashMap<String, LinkHint>>,
+  /// Hop distance of the page from the start URL, which is at 0.
+  pub parent_depth: Option<u32>,
+  /// The page's robots meta tags or `X-Robots-Tag` said `nofollow`.
+  pub nofollow: Option<bool>,
+  /// Groups of URLs for the same content in different languages: the page's
+  /// `<link rel="alternate" hreflang>` tags, or the `alternates` of sitemap entries.
+  pub locale_alternates: Option<Vec<Vec<SitemapAlternate>>>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Default)]
 #[napi(object)]
 pub struct FilterUrlCall {
   pub href: String,
   pub url: String,
+  /// As with `CrawlFilter::filter_url`, options that judge a link against the crawl as a whole,
+  /// such as `includes` and `max_depth`, don't apply to a single URL.
+  pub options: CrawlFilterOptions,
+}
+
+#[derive(Serialize)]
+#[napi(object)]
+pub struct FilterUrlResult {
+  pub allowed: bool,
+  pub url: Option<String>,
+  pub denial_reason: Option<String>,
+  /// `denial_reason` with the rule that matched.
+  pub denial: Option<LinkDenial>,
+  /// robots.txt group that was applied: one of `robots_user_agents`, or `*`.
+  pub robots_user_agent: Option<String>,
+}
+
+impl FilterUrlResult {
+  fn allowed(url: String) -> Self {
+    Self {
+      allowed: true,
+      url: Some(url),
+      denial_reason: None,
+      denial: None,
+      robots_user_agent: None,
+    }
+  }
+
+  fn denied(denial: Denial, url: Option<String>) -> Self {
+    Self {
+      allowed: false,
+      url: None,
+      denial_reason: Some(denial.code.to_string()),
+      denial: Some(denial.into_link_denial(url)),
+      robots_user_agent: None,
+    }
+  }
+}
+
+/// Changes to one of the built-in deny lists.
+#[derive(Deserialize, Default, Clone)]
+#[napi(object)]
+pub struct DenyListOverride {
+  /// Entries to deny on top of the defaults.
+  pub add: Option<Vec<String>>,
+  /// Default entries to allow again.
+  pub remove: Option<Vec<String>>,
+}
+
+/// Crawl configuration shared by `filter_links`, `filter_url` and `CrawlFilter`.
+#[derive(Deserialize, Default)]
+#[napi(object)]
+pub struct CrawlFilterOptions {
+  pub max_depth: u32,
+  /// Maximum hop distance from the start URL; deeper links are denied as
+  /// `DISCOVERY_DEPTH_LIMIT`. Independent of the path depth limited by `max_depth`. Only
+  /// applies to pages with a `parent_depth`; links from other pages, such as seeds, are not
+  /// checked.
+  pub max_discovery_depth: Option<u32>,
   pub base_url: String,
+  pub initial_url: String,
+  pub regex_on_full_url: bool,
   pub excludes: Vec<String>,
+  pub includes: Vec<String>,
+  pub allow_backward_crawling: bool,
   pub ignore_robots_txt: bool,
   pub robots_txt: String,
   pub allow_external_content_links: bool,
   pub allow_subdomains: bool,
+  /// Which hosts count as internal: `exact`, `www` (default, ignores a leading `www.`) or
+  /// `registrable_domain` (any host under the same eTLD+1).
+  pub host_scope: Option<String>,
+  /// Extra hosts treated as internal for every check, including depth, include/exclude and
+  /// backward crawling. `*.example.com` matches any subdomain; `*` elsewhere stays within a label.
+  pub allowed_hosts: Option<Vec<String>>,
+  /// User agents to evaluate robots.txt as, in priority order. Defaults to the Firecrawl agent.
+  pub robots_user_agents: Option<Vec<String>>,
+  /// Fail on `includes`/`excludes` patterns that don't compile instead of ignoring them.
+  pub strict_patterns: Option<bool>,
+  /// Syntax of `includes`/`excludes`: `regex` (default) or `glob` (gitignore-style, `!` negates).
+  /// Globs test the path, or `//host/path` for external links, so `/blog/**` only applies to
+  /// the crawled site.
+  pub pattern_syntax: Option<String>,
+  /// Deny links whose normalized form was already accepted in the same call as `DUPLICATE`.
+  pub normalization: Option<UrlNormalizationOptions>,
+  /// Changes to the file extensions denied as `FILE_TYPE`, e.g. `{ remove: [".xlsx"] }`.
+  pub file_extensions: Option<DenyListOverride>,
+  /// Changes to the domains denied as `SOCIAL_MEDIA`, e.g. `{ remove: ["github.com"] }`.
+  pub social_media_domains: Option<DenyListOverride>,
+  /// Link categories to follow, e.g. `["page", "document"]`. Replaces the extension-only
+  /// `FILE_TYPE` check; links in other categories are denied as `FILE_TYPE`. Extensions listed
+  /// in `file_extensions` still win: `add` denies and `remove` allows them in any category.
+  pub allowed_categories: Option<Vec<String>>,
+  /// Content types already seen for absolute URLs, used to classify links.
+  pub content_types: Option<HashMap<String, String>>,
+  /// Rank allowed links best-first before applying `limit`, instead of keeping input order.
+  pub scoring: Option<LinkScoringOptions>,
+  /// Deny links marked `rel="nofollow"` in `link_hints`, and every link of a page marked
+  /// `nofollow`, as `NOFOLLOW`.
+  pub respect_nofollow: Option<bool>,
+  /// Which URL fragments to keep: `drop` (none), `hashbang` (`#!` routes), `routes` (`#!` and
+  /// `#/` routes) or `pattern` (those matching `fragment_pattern`). Other fragments are removed
+  /// and the rewritten URL is returned. By default fragments containing `/` are kept and other
+  /// internal links with a fragment are denied as `SECTION_LINK`.
+  pub fragment_policy: Option<String>,
+  /// Regex tested against the fragment, without `#`, for the `pattern` fragment policy.
+  pub fragment_pattern: Option<String>,
+  /// Language tags to crawl, most preferred first, e.g. `["en-US", "en"]`. Of each group in
+  /// `locale_alternates` only the best match, or else the `x-default`, is kept; its alternates
+  /// are denied as `LOCALE_ALTERNATE`. Only alternates among `links` that pass the other rules
+  /// are candidates, so a group falls back to its next best when the best isn't linked.
+  pub preferred_locales: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Default)]
+#[napi(object)]
+pub struct ValidatePatternsCall {
+  pub includes: Vec<String>,
+  pub excludes: Vec<String>,
+  /// `regex` (default) or `glob`.
+  pub pattern_syntax: Option<String>,
 }
 
 #[derive(Serialize)]
 #[napi(object)]
-pub struct FilterUrlResult {
-  pub allowed: bool,
-  pub url: Option<String>,
-  pub denial_reason: Option<String>,
+pub struct PatternError {
+  /// `includes` or `excludes`.
+  pub list: String,
+  pub index: u32,
+  pub pattern: String,
+  pub message: String,
+  /// Character offsets of the offending part of the pattern, when the parser reports one.
+  pub start: Option<u32>,
+  pub end: Option<u32>,
+}
+
+#[derive(Serialize)]
+#[napi(object)]
+pub struct PatternValidationResult {
+  pub valid: bool,
+  pub errors: Vec<PatternError>,
+}
+
+/// What the page's `<a>` element said about a link.
+#[derive(Deserialize, Default, Clone)]
+#[napi(object)]
+pub struct LinkHint {
+  /// The `type` attribute, e.g. `application/pdf`.
+  pub mime_type: Option<String>,
+  /// The `download` attribute; an empty string when it is present without a filename.
+  pub download: Option<String>,
+  /// The link text, matched against `scoring` keywords.
+  pub anchor_text: Option<String>,
+  /// `<priority>` from the sitemap that listed the link, between 0 and 1.
+  pub sitemap_priority: Option<f64>,
+  /// `rel` tokens, e.g. `nofollow`.
+  pub rel: Option<Vec<String>>,
+  /// The `aria-label` attribute.
+  pub aria_label: Option<String>,
 }
 
+/// Why a link was denied, in more detail than the bare code in `denial_reasons`.
 #[derive(Serialize, Debug)]
 #[napi(object)]
+pub struct LinkDenial {
+  pub code: String,
+  /// The resolved URL, normalized when `normalization` is set.
+  pub url: Option<String>,
+  /// `path` or `url`: which part of the URL the rule was tested against.
+  pub tested: Option<String>,
+  pub tested_value: Option<String>,
+  /// Index into `includes`/`excludes` of the pattern that decided.
+  pub pattern_index: Option<u32>,
+  /// The pattern, social media domain or URL template that matched, or for
+  /// `LOCALE_ALTERNATE` the alternate that is kept instead.
+  pub pattern: Option<String>,
+  /// The robots.txt rule that disallowed the link: the same one `parse_robots_txt` reports.
+  pub robots_rule: Option<RobotsRule>,
+}
+
+/// `<image:image>` extension of a sitemap `<url>` entry.
+#[derive(Serialize, Debug, Default)]
+#[napi(object)]
+pub struct SitemapImage {
+  pub loc: String,
+  pub title: Option<String>,
+  pub caption: Option<String>,
+  pub geo_location: Option<String>,
+  pub license: Option<String>,
+}
+
+/// `<news:news>` extension of a sitemap `<url>` entry.
+#[derive(Serialize, Debug, Default)]
+#[napi(object)]
+pub struct SitemapNews {
+  pub publication_name: Option<String>,
+  pub publication_language: Option<String>,
+  pub publication_date: Option<String>,
+  pub title: Option<String>,
+  pub keywords: Option<String>,
+}
+
+/// `<video:video>` extension of a sitemap `<url>` entry.
+#[derive(Serialize, Debug, Default)]
+#[napi(object)]
+pub struct SitemapVideo {
+  pub thumbnail_loc: Option<String>,
+  pub title: Option<String>,
+  pub description: Option<String>,
+  pub content_loc: Option<String>,
+  pub player_loc: Option<String>,
+  pub duration: Option<u32>,
+  pub publication_date: Option<String>,
+  pub family_friendly: Option<bool>,
+}
+
+/// `<xhtml:link rel="alternate" hreflang="..">` entry of a sitemap `<url>`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+#[napi(object)]
+pub struct SitemapAlternate {
+  pub hreflang: String,
+  pub href: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+#[napi(object)]
 pub struct SitemapUrl {
   pub loc: Vec<String>,
+  pub lastmod: Option<String>,
+  pub changefreq: Option<String>,
+  pub priority: Option<f64>,
+  pub images: Vec<SitemapImage>,
+  pub news: Option<SitemapNews>,
+  pub videos: Vec<SitemapVideo>,
+  pub alternates: Vec<SitemapAlternate>,
 }
 
 #[derive(Serialize, Debug)]
 #[napi(object)]
 pub struct SitemapEntry {
   pub loc: Vec<String>,
+  pub lastmod: Option<String>,
 }
 
 #[derive(Serialize, Debug)]
@@ -94,6 +348,8 @@ pub struct SitemapIndex {
 pub struct ParsedSitemap {
   pub urlset: Option<SitemapUrlset>,
   pub sitemapindex: Option<SitemapIndex>,
+  /// `xml`, `rss` or `atom`. Feed items are returned as `urlset` entries.
+  pub format: String,
 }
 
 #[derive(Serialize, Debug)]
@@ -102,6 +358,8 @@ pub struct SitemapInstruction {
   pub action: String,
   pub urls: Vec<String>,
   pub count: u32,
+  /// Parsed sitemap metadata for each entry of `urls`, in the same order.
+  pub entries: Vec<SitemapUrl>,
 }
 
 #[derive(Serialize, Debug)]
@@ -109,9 +367,30 @@ pub struct SitemapInstruction {
 pub struct SitemapProcessingResult {
   pub instructions: Vec<SitemapInstruction>,
   pub total_count: u32,
+  /// Number of entries left out because their `<lastmod>` is not newer than `since` / the known lastmod.
+  pub skipped_count: u32,
+  /// Whether parsing stopped early because a size limit was hit.
+  pub truncated: bool,
+  /// `MAX_URLS` or `MAX_BYTES` when `truncated` is set.
+  pub truncation_reason: Option<String>,
+  /// Detected input format: `xml`, `text`, `rss` or `atom`.
+  pub format: String,
+}
+
+#[derive(Deserialize, Default)]
+#[napi(object)]
+pub struct SitemapProcessOptions {
+  /// Only return entries whose `<lastmod>` is strictly newer than this W3C datetime.
+  pub since: Option<String>,
+  /// Last known `<lastmod>` per URL (page or child sitemap). Takes precedence over `since`.
+  pub known_lastmod: Option<HashMap<String, String>>,
+  /// Maximum number of entries to read, 50,000 by default. Only enforced by `process_sitemap_bytes`.
+  pub max_urls: Option<u32>,
+  /// Maximum number of uncompressed bytes to read, 50 MiB by default. Only enforced by `process_sitemap_bytes`.
+  pub max_bytes: Option<u32>,
 }
 
-const URL_PARSE_ERROR: &str = "URL_PARSE_ERROR";
+pub(crate) const URL_PARSE_ERROR: &str = "URL_PARSE_ERROR";
 const DEPTH_LIMIT: &str = "DEPTH_LIMIT";
 const EXCLUDE_PATTERN: &str = "EXCLUDE_PATTERN";
 const INCLUDE_PATTERN: &str = "INCLUDE_PATTERN";
@@ -122,6 +401,11 @@ const SOCIAL_MEDIA: &str = "SOCIAL_MEDIA";
 const EXTERNAL_LINK: &str = "EXTERNAL_LINK";
 const SECTION_LINK: &str = "SECTION_LINK";
 const NON_WEB_PROTOCOL: &str = "NON_WEB_PROTOCOL";
+pub(crate) const DUPLICATE: &str = "DUPLICATE";
+const DISCOVERY_DEPTH_LIMIT: &str = "DISCOVERY_DEPTH_LIMIT";
+pub(crate) const URL_TRAP: &str = "URL_TRAP";
+const NOFOLLOW: &str = "NOFOLLOW";
+const LOCALE_ALTERNATE: &str = "LOCALE_ALTERNATE";
 
 #[inline]
 fn is_file(path: &str) -> bool {
@@ -134,7 +418,7 @@ fn is_file(path: &str) -> bool {
 }
 
 #[inline]
-fn get_url_depth(path: &str) -> u32 {
+pub(crate) fn get_url_depth(path: &str) -> u32 {
   path
     .split('/')
     .filter(|segment| !segment.is_empty() && *segment != "index.php" && *segment != "index.html")
@@ -142,23 +426,7 @@ fn get_url_depth(path: &str) -> u32 {
 }
 
 #[inline]
-fn is_internal_link(url: &Url, base_url: &Url) -> bool {
-  let base_domain = base_url
-    .host_str()
-    .unwrap_or("")
-    .trim_start_matches("www.")
-    .trim();
-  let link_domain = url
-    .host_str()
-    .unwrap_or("")
-    .trim_start_matches("www.")
-    .trim();
-
-  link_domain == base_domain
-}
-
-#[inline]
-fn no_sections(url_str: &str) -> bool {
+pub(crate) fn no_sections(url_str: &str) -> bool {
   if !url_str.contains('#') {
     return true;
   }
@@ -171,6 +439,72 @@ fn no_sections(url_str: &str) -> bool {
   }
 }
 
+const FRAGMENT_POLICY_DROP: &str = "drop";
+const FRAGMENT_POLICY_HASHBANG: &str = "hashbang";
+const FRAGMENT_POLICY_ROUTES: &str = "routes";
+const FRAGMENT_POLICY_PATTERN: &str = "pattern";
+
+/// Which fragments identify a page of their own and are kept; all others are dropped.
+#[derive(Clone)]
+pub(crate) enum FragmentPolicy {
+  /// None.
+  Drop,
+  /// `#!` hashbang routes.
+  Hashbang,
+  /// `#!` and `#/` routes, but not in-page anchors such as `#section/intro`.
+  Routes,
+  /// Fragments matching a per-site regex.
+  Pattern(Regex),
+}
+
+impl FragmentPolicy {
+  fn parse(
+    policy: Option<&str>,
+    pattern: Option<&str>,
+  ) -> std::result::Result<Option<Self>, String> {
+    let policy = match (policy, pattern) {
+      (None, None) => return Ok(None),
+      (None | Some(FRAGMENT_POLICY_PATTERN), Some(pattern)) => Self::Pattern(
+        Regex::new(pattern).map_err(|e| format!("Invalid fragment pattern '{pattern}': {e}"))?,
+      ),
+      (Some(FRAGMENT_POLICY_PATTERN), None) => {
+        return Err(format!(
+          "Fragment policy '{FRAGMENT_POLICY_PATTERN}' requires a fragment pattern"
+        ))
+      }
+      (Some(policy), Some(_)) => {
+        return Err(format!(
+          "A fragment pattern only applies to fragment policy '{FRAGMENT_POLICY_PATTERN}', not '{policy}'"
+        ))
+      }
+      (Some(FRAGMENT_POLICY_DROP), None) => Self::Drop,
+      (Some(FRAGMENT_POLICY_HASHBANG), None) => Self::Hashbang,
+      (Some(FRAGMENT_POLICY_ROUTES), None) => Self::Routes,
+      (Some(other), None) => {
+        return Err(format!(
+          "Unknown fragment policy '{other}', expected '{FRAGMENT_POLICY_DROP}', '{FRAGMENT_POLICY_HASHBANG}', '{FRAGMENT_POLICY_ROUTES}' or '{FRAGMENT_POLICY_PATTERN}'"
+        ))
+      }
+    };
+    Ok(Some(policy))
+  }
+
+  fn keeps(&self, fragment: &str) -> bool {
+    match self {
+      Self::Drop => false,
+      Self::Hashbang => fragment.starts_with('!'),
+      Self::Routes => fragment.starts_with('!') || fragment.starts_with('/'),
+      Self::Pattern(regex) => regex.is_match(fragment),
+    }
+  }
+
+  pub(crate) fn apply(&self, url: &mut Url) {
+    if url.fragment().is_some_and(|fragment| !self.keeps(fragment)) {
+      url.set_fragment(None);
+    }
+  }
+}
+
 #[inline]
 fn is_non_web_protocol(url_str: &str) -> bool {
   const NON_WEB_PROTOCOLS: &[&str] = &[
@@ -182,35 +516,144 @@ fn is_non_web_protocol(url_str: &str) -> bool {
     .any(|protocol| url_str.starts_with(protocol))
 }
 
-#[inline]
-fn is_social_media_or_email(url_str: &str) -> bool {
-  const SOCIAL_MEDIA_OR_EMAIL: &[&str] = &[
-    "facebook.com",
-    "twitter.com",
-    "linkedin.com",
-    "instagram.com",
-    "pinterest.com",
-    "github.com",
-    "calendly.com",
-    "discord.gg",
-    "discord.com",
-  ];
+const HOST_SCOPE_EXACT: &str = "exact";
+const HOST_SCOPE_WWW: &str = "www";
+const HOST_SCOPE_REGISTRABLE_DOMAIN: &str = "registrable_domain";
 
-  SOCIAL_MEDIA_OR_EMAIL
-    .iter()
-    .any(|domain| url_str.contains(domain))
+#[derive(Clone, Copy, PartialEq)]
+enum HostScopeMode {
+  Exact,
+  Www,
+  RegistrableDomain,
+}
+
+impl HostScopeMode {
+  fn parse(value: Option<&str>) -> std::result::Result<Self, String> {
+    match value {
+      Some(HOST_SCOPE_EXACT) => Ok(Self::Exact),
+      None | Some(HOST_SCOPE_WWW) => Ok(Self::Www),
+      Some(HOST_SCOPE_REGISTRABLE_DOMAIN) => Ok(Self::RegistrableDomain),
+      Some(other) => Err(format!(
+        "Unknown host scope '{other}', expected '{HOST_SCOPE_EXACT}', '{HOST_SCOPE_WWW}' or '{HOST_SCOPE_REGISTRABLE_DOMAIN}'"
+      )),
+    }
+  }
+}
+
+/// Lowercases and IDNA-encodes a host written by hand, e.g. `Bücher.Example.` becomes
+/// `xn--bcher-kva.example`.
+pub(crate) fn normalize_host(host: &str) -> Option<String> {
+  let host = host.trim().trim_end_matches('.');
+  match url::Host::parse(host).ok()? {
+    url::Host::Domain(domain) => Some(domain),
+    url::Host::Ipv4(ip) => Some(ip.to_string()),
+    url::Host::Ipv6(ip) => Some(format!("[{ip}]")),
+  }
 }
 
+/// The host of a parsed URL. `Url` has already lowercased and IDNA-encoded it, so only a
+/// trailing dot is left to remove.
 #[inline]
-fn is_subdomain(url: &Url, base_url: &Url) -> bool {
-  match (url.host_str(), base_url.host_str()) {
-    (Some(link_host), Some(base_host)) => {
-      match (psl::domain_str(link_host), psl::domain_str(base_host)) {
-        (Some(link_domain), Some(base_domain)) => link_domain == base_domain,
-        _ => false,
+pub(crate) fn url_host(url: &Url) -> &str {
+  url.host_str().unwrap_or("").trim_end_matches('.')
+}
+
+/// Compiles an allowed-host wildcard. A leading `*.` matches one or more labels, any other
+/// `*` matches within a single label.
+fn host_pattern_regex(pattern: &str) -> std::result::Result<Regex, String> {
+  let (prefix, rest) = match pattern.strip_prefix("*.") {
+    Some(rest) => ("^(?:[^.]+\\.)+", rest),
+    None => ("^", pattern),
+  };
+  let body = rest
+    .split('*')
+    .map(regex_syntax::escape)
+    .collect::<Vec<_>>()
+    .join("[^.]*");
+  Regex::new(&format!("{prefix}{body}$"))
+    .map_err(|e| format!("Invalid allowed host '{pattern}': {e}"))
+}
+
+/// Decides which hosts count as the crawled site. Every internal/subdomain/external decision
+/// goes through here so they agree with each other.
+struct HostScope {
+  mode: HostScopeMode,
+  base_key: String,
+  base_domain: Option<String>,
+  allowed_hosts: HashSet<String>,
+  allowed_host_patterns: Vec<Regex>,
+}
+
+impl HostScope {
+  fn new(
+    base_url: &Url,
+    mode: Option<&str>,
+    allowed_hosts: Option<&[String]>,
+  ) -> std::result::Result<Self, String> {
+    let mode = HostScopeMode::parse(mode)?;
+    let base_host = url_host(base_url);
+
+    let mut scope = Self {
+      mode,
+      base_key: Self::key(mode, base_host).to_string(),
+      base_domain: psl::domain_str(base_host).map(str::to_string),
+      allowed_hosts: HashSet::new(),
+      allowed_host_patterns: Vec::new(),
+    };
+
+    for host in allowed_hosts.unwrap_or_default() {
+      let normalized = normalize_host(host)
+        .filter(|normalized| !normalized.is_empty())
+        .ok_or_else(|| format!("Invalid allowed host '{host}'"))?;
+      let normalized = scope.allowed_key(&normalized).to_string();
+      if normalized.contains('*') {
+        scope
+          .allowed_host_patterns
+          .push(host_pattern_regex(&normalized)?);
+      } else {
+        scope.allowed_hosts.insert(normalized);
       }
     }
-    _ => false,
+
+    Ok(scope)
+  }
+
+  /// Allowed hosts ignore a leading `www.` unless the scope is `exact`.
+  fn allowed_key<'a>(&self, host: &'a str) -> &'a str {
+    match self.mode {
+      HostScopeMode::Exact => host,
+      _ => host.strip_prefix("www.").unwrap_or(host),
+    }
+  }
+
+  fn is_allowed_host(&self, host: &str) -> bool {
+    let host = self.allowed_key(host);
+    self.allowed_hosts.contains(host)
+      || self
+        .allowed_host_patterns
+        .iter()
+        .any(|pattern| pattern.is_match(host))
+  }
+
+  fn key(mode: HostScopeMode, host: &str) -> &str {
+    match mode {
+      HostScopeMode::Exact => host,
+      HostScopeMode::Www => host.strip_prefix("www.").unwrap_or(host),
+      HostScopeMode::RegistrableDomain => psl::domain_str(host).unwrap_or(host),
+    }
+  }
+
+  fn is_internal(&self, url: &Url) -> bool {
+    let host = url_host(url);
+    Self::key(self.mode, host) == self.base_key || self.is_allowed_host(host)
+  }
+
+  /// Whether `url` is on the same registrable domain as the base URL, whatever the mode.
+  fn is_subdomain(&self, url: &Url) -> bool {
+    match (psl::domain_str(url_host(url)), &self.base_domain) {
+      (Some(link_domain), Some(base_domain)) => link_domain == base_domain,
+      _ => false,
+    }
   }
 }
 
@@ -227,819 +670,3980 @@ fn is_external_main_page(url_str: &str) -> bool {
   }
 }
 
-fn _filter_links(data: FilterLinksCall) -> std::result::Result<FilterLinksResult, String> {
-  let limit = data.limit.map_or(usize::MAX, |x| x.max(0) as usize);
-  if limit == 0 {
-    return Ok(FilterLinksResult {
-      links: Vec::new(),
-      denial_reasons: HashMap::new(),
-    });
-  }
-
-  let base_url = Url::parse(&data.base_url).map_err(|e| format!("Base URL parse error: {e}"))?;
-  let initial_url =
-    Url::parse(&data.initial_url).map_err(|e| format!("Initial URL parse error: {e}"))?;
-  let initial_path = initial_url.path();
+/// Applies `overrides` to a default deny list. Entries are trimmed, lowercased and passed
+/// through `normalize` before they are compared.
+fn apply_deny_list_override<C: FromIterator<String>>(
+  defaults: &[&str],
+  overrides: Option<&DenyListOverride>,
+  normalize: impl Fn(&str) -> String,
+) -> C {
+  let (add, remove) = deny_list_override_entries(overrides, normalize);
 
-  let excludes_regex: Vec<Regex> = data
-    .excludes
-    .iter()
-    .filter_map(|e| Regex::new(e).ok())
-    .collect();
-  let includes_regex: Vec<Regex> = data
-    .includes
+  defaults
     .iter()
-    .filter_map(|i| Regex::new(i).ok())
-    .collect();
+    .map(|entry| entry.to_string())
+    .chain(add)
+    .filter(|entry| !remove.contains(entry))
+    .collect()
+}
 
-  let robot = if !data.ignore_robots_txt && !data.robots_txt.is_empty() {
-    Robot::new("FireCrawlAgent", data.robots_txt.as_bytes())
-      .ok()
-      .or_else(|| Robot::new("FirecrawlAgent", data.robots_txt.as_bytes()).ok())
-  } else {
-    None
+/// The `add` and `remove` entries of `overrides`, normalized as in `apply_deny_list_override`.
+fn deny_list_override_entries(
+  overrides: Option<&DenyListOverride>,
+  normalize: impl Fn(&str) -> String,
+) -> (HashSet<String>, HashSet<String>) {
+  let entries = |entries: &Option<Vec<String>>| {
+    entries
+      .iter()
+      .flatten()
+      .map(|entry| normalize(&entry.trim().to_lowercase()))
+      .collect()
   };
+  match overrides {
+    Some(overrides) => (entries(&overrides.add), entries(&overrides.remove)),
+    None => (HashSet::new(), HashSet::new()),
+  }
+}
 
-  let mut result_links = Vec::new();
-  let mut denial_reasons = HashMap::new();
+fn normalize_file_extension(extension: &str) -> String {
+  format!(".{}", extension.trim_start_matches('.'))
+}
 
-  for link in data.links {
-    if result_links.len() >= limit {
-      break;
-    }
+const INCLUDES: &str = "includes";
+const EXCLUDES: &str = "excludes";
 
-    let url = match base_url.join(&link) {
-      Ok(url) => url,
-      Err(_) => {
-        denial_reasons.insert(link, URL_PARSE_ERROR.to_string());
-        continue;
-      }
-    };
+const PATTERN_SYNTAX_REGEX: &str = "regex";
+const PATTERN_SYNTAX_GLOB: &str = "glob";
 
-    let path = url.path();
-    let url_str = url.as_str();
+#[derive(Clone, Copy, PartialEq)]
+enum PatternSyntax {
+  Regex,
+  Glob,
+}
 
-    if is_non_web_protocol(url_str) {
-      denial_reasons.insert(link, NON_WEB_PROTOCOL.to_string());
-      continue;
+impl PatternSyntax {
+  fn parse(value: Option<&str>) -> std::result::Result<Self, String> {
+    match value {
+      None | Some(PATTERN_SYNTAX_REGEX) => Ok(Self::Regex),
+      Some(PATTERN_SYNTAX_GLOB) => Ok(Self::Glob),
+      Some(other) => Err(format!(
+        "Unknown pattern syntax '{other}', expected '{PATTERN_SYNTAX_REGEX}' or '{PATTERN_SYNTAX_GLOB}'"
+      )),
     }
+  }
+}
 
-    if get_url_depth(path) > data.max_depth {
-      denial_reasons.insert(link, DEPTH_LIMIT.to_string());
-      continue;
+/// Why a pattern failed to compile, with the character span at fault when it is known.
+struct PatternCompileError {
+  message: String,
+  span: Option<(u32, u32)>,
+}
+
+impl PatternCompileError {
+  fn from_regex(pattern: &str, error: regex::Error) -> Self {
+    // `regex::Error` only carries a rendered message, so reparse to recover the span.
+    let syntax_error = match regex_syntax::Parser::new().parse(pattern) {
+      Err(regex_syntax::Error::Parse(e)) => Some((e.kind().to_string(), *e.span())),
+      Err(regex_syntax::Error::Translate(e)) => Some((e.kind().to_string(), *e.span())),
+      _ => None,
+    };
+    let char_offset = |offset: usize| pattern[..offset].chars().count() as u32;
+
+    match syntax_error {
+      Some((message, span)) => Self {
+        message,
+        span: Some((char_offset(span.start.offset), char_offset(span.end.offset))),
+      },
+      None => Self {
+        message: error.to_string(),
+        span: None,
+      },
     }
+  }
+}
 
-    if is_file(path) {
-      denial_reasons.insert(link, FILE_TYPE.to_string());
-      continue;
+impl PatternError {
+  fn new(list: &str, index: usize, pattern: &str, error: PatternCompileError) -> Self {
+    Self {
+      list: list.to_string(),
+      index: index as u32,
+      pattern: pattern.to_string(),
+      message: error.message,
+      start: error.span.map(|(start, _)| start),
+      end: error.span.map(|(_, end)| end),
     }
+  }
+}
 
-    if is_internal_link(&url, &base_url) {
-      // INTERNAL LINKS
-      if !no_sections(url_str) {
-        denial_reasons.insert(link, SECTION_LINK.to_string());
-        continue;
-      }
+pub(crate) struct CompiledPattern {
+  /// Position in the list as given, which still counts patterns that failed to compile.
+  index: usize,
+  source: String,
+  pub(crate) regex: Regex,
+  /// Glob patterns starting with `!` re-include what earlier patterns in the list matched.
+  negated: bool,
+}
+
+/// Translates a gitignore-style glob into an anchored regex over the URL path.
+///
+/// `*` and `?` stay within one path segment, `**` spans segments, a leading `/` anchors the
+/// pattern at the site root (otherwise it may match at any depth), and a match also covers
+/// everything below the matched path, like a directory in `.gitignore`.
+fn glob_to_regex(glob: &str) -> std::result::Result<String, PatternCompileError> {
+  let chars: Vec<char> = glob.chars().collect();
+  if chars.is_empty() {
+    return Err(PatternCompileError {
+      message: "empty glob pattern".to_string(),
+      span: None,
+    });
+  }
 
-      if !data.allow_backward_crawling && !path.starts_with(initial_path) {
-        denial_reasons.insert(link, BACKWARD_CRAWLING.to_string());
+  let mut regex = String::from(if chars[0] == '/' { "^" } else { "^.*/" });
+  let mut i = 0;
+  while i < chars.len() {
+    match chars[i] {
+      '*' => {
+        let start = i;
+        while i < chars.len() && chars[i] == '*' {
+          i += 1;
+        }
+        let at_segment_start = start == 0 || chars[start - 1] == '/';
+        let at_segment_end = i == chars.len() || chars[i] == '/';
+        if i - start >= 2 && at_segment_start && at_segment_end {
+          if i == chars.len() {
+            // A trailing `/**` matches the directory itself as well as everything in it.
+            if regex.ends_with('/') {
+              regex.pop();
+            }
+            regex.push_str("(?:/.*)?");
+          } else {
+            i += 1;
+            regex.push_str("(?:.*/)?");
+          }
+        } else {
+          regex.push_str("[^/]*");
+        }
         continue;
       }
+      '?' => regex.push_str("[^/]"),
+      '[' => {
+        let start = i;
+        i += 1;
+        let mut class = String::from("[");
+        if i < chars.len() && (chars[i] == '!' || chars[i] == '^') {
+          class.push('^');
+          i += 1;
+        }
+        let class_start = i;
+        while i < chars.len() && (chars[i] != ']' || i == class_start) {
+          match chars[i] {
+            '\\' | '[' | ']' | '^' | '&' | '~' => {
+              class.push('\\');
+              class.push(chars[i]);
+            }
+            c => class.push(c),
+          }
+          i += 1;
+        }
+        if i == chars.len() {
+          return Err(PatternCompileError {
+            message: "unclosed character class".to_string(),
+            span: Some((start as u32, chars.len() as u32)),
+          });
+        }
+        class.push(']');
+        regex.push_str(&class);
+      }
+      '\\' => {
+        i += 1;
+        let Some(&escaped) = chars.get(i) else {
+          return Err(PatternCompileError {
+            message: "dangling escape".to_string(),
+            span: Some(((i - 1) as u32, i as u32)),
+          });
+        };
+        regex.push_str(&regex_syntax::escape(&escaped.to_string()));
+      }
+      c => regex.push_str(&regex_syntax::escape(&c.to_string())),
+    }
+    i += 1;
+  }
 
-      let match_target = if data.regex_on_full_url {
-        url_str
-      } else {
-        path
+  if regex.ends_with('/') {
+    regex.push_str(".*$");
+  } else {
+    regex.push_str("(?:/.*)?$");
+  }
+  Ok(regex)
+}
+
+fn compile_pattern(
+  pattern: &str,
+  syntax: PatternSyntax,
+) -> std::result::Result<CompiledPattern, PatternCompileError> {
+  match syntax {
+    PatternSyntax::Regex => Regex::new(pattern)
+      .map(|regex| CompiledPattern {
+        index: 0,
+        source: pattern.to_string(),
+        regex,
+        negated: false,
+      })
+      .map_err(|e| PatternCompileError::from_regex(pattern, e)),
+    PatternSyntax::Glob => {
+      let (negated, glob) = match pattern.strip_prefix('!') {
+        Some(glob) => (true, glob),
+        None => (false, pattern),
       };
+      let shift = |(start, end): (u32, u32)| (start + negated as u32, end + negated as u32);
+      let regex = glob_to_regex(glob).map_err(|e| PatternCompileError {
+        message: e.message,
+        span: e.span.map(shift),
+      })?;
+      Regex::new(&regex)
+        .map(|regex| CompiledPattern {
+          index: 0,
+          source: pattern.to_string(),
+          regex,
+          negated,
+        })
+        .map_err(|e| PatternCompileError {
+          message: e.to_string(),
+          span: None,
+        })
+    }
+  }
+}
 
-      if !excludes_regex.is_empty() && excludes_regex.iter().any(|r| r.is_match(match_target)) {
-        denial_reasons.insert(link, EXCLUDE_PATTERN.to_string());
-        continue;
-      }
+/// The outcome of testing a URL against a `PatternSet`.
+struct PatternMatch<'a> {
+  matched: bool,
+  /// Position in `PatternSet::patterns` of the pattern that decided, if any matched.
+  index: Option<usize>,
+  tested: &'static str,
+  value: &'a str,
+}
 
-      if !includes_regex.is_empty() && !includes_regex.iter().any(|r| r.is_match(match_target)) {
-        denial_reasons.insert(link, INCLUDE_PATTERN.to_string());
-        continue;
-      }
+/// A compiled `includes` or `excludes` list.
+pub(crate) struct PatternSet {
+  syntax: PatternSyntax,
+  pub(crate) patterns: Vec<CompiledPattern>,
+}
 
-      if let Some(ref robot) = robot {
-        if !robot.allowed(url_str) {
-          denial_reasons.insert(link, ROBOTS_TXT.to_string());
-          continue;
-        }
-      }
+impl PatternSet {
+  fn is_empty(&self) -> bool {
+    self.patterns.is_empty()
+  }
 
-      result_links.push(link);
-    } else {
-      // EXTERNAL LINKS
-      if is_social_media_or_email(url_str) {
-        denial_reasons.insert(link, SOCIAL_MEDIA.to_string());
-        continue;
+  /// Regexes test the path, or the full URL when `full_url` is set. Globs always test the
+  /// path; the last glob that matches decides, and a list made only of `!` globs starts from
+  /// matching everything. Links outside the crawled site go through `test_external`.
+  fn test<'a>(&self, url: &'a Url, full_url: bool) -> PatternMatch<'a> {
+    match self.syntax {
+      PatternSyntax::Regex => {
+        let (tested, value) = if full_url {
+          (TESTED_URL, url.as_str())
+        } else {
+          (TESTED_PATH, url.path())
+        };
+        let index = self.patterns.iter().position(|p| p.regex.is_match(value));
+        PatternMatch {
+          matched: index.is_some(),
+          index,
+          tested,
+          value,
+        }
       }
+      PatternSyntax::Glob => self.test_glob(url.path(), TESTED_PATH),
+    }
+  }
 
-      if !excludes_regex.is_empty() && excludes_regex.iter().any(|r| r.is_match(url_str)) {
-        denial_reasons.insert(link, EXCLUDE_PATTERN.to_string());
-        continue;
+  /// Tests a link outside the crawled site. Regexes test the full URL; globs test `//host/path`,
+  /// so a root-anchored glob like `/blog/**` only applies to the crawled site, while
+  /// `//other.com/**` or `**/blog/**` can match other hosts.
+  fn test_external<'a>(&self, url: &'a Url) -> PatternMatch<'a> {
+    match self.syntax {
+      PatternSyntax::Regex => self.test(url, true),
+      PatternSyntax::Glob => {
+        let url_str = url.as_str();
+        let start = url_str.find("//").unwrap_or(0);
+        let end = url_str.find(['?', '#']).unwrap_or(url_str.len());
+        self.test_glob(&url_str[start..end], TESTED_URL)
       }
+    }
+  }
 
-      if is_internal_link(&initial_url, &base_url)
-        && data.allow_external_content_links
-        && !is_external_main_page(url_str)
-      {
-        result_links.push(link);
-        continue;
+  fn test_glob<'a>(&self, value: &'a str, tested: &'static str) -> PatternMatch<'a> {
+    let mut matched = self.patterns.iter().all(|p| p.negated);
+    let mut index = None;
+    for (i, pattern) in self.patterns.iter().enumerate() {
+      if pattern.regex.is_match(value) {
+        matched = !pattern.negated;
+        index = Some(i);
       }
+    }
+    PatternMatch {
+      matched,
+      index,
+      tested,
+      value,
+    }
+  }
+}
 
-      if data.allow_subdomains
-        && !is_social_media_or_email(url_str)
-        && is_subdomain(&url, &base_url)
-      {
-        // When allowing subdomains, still honor include patterns
-        let match_target = if data.regex_on_full_url {
-          url_str
-        } else {
-          path
-        };
-        if !includes_regex.is_empty() && !includes_regex.iter().any(|r| r.is_match(match_target)) {
-          denial_reasons.insert(link, INCLUDE_PATTERN.to_string());
-          continue;
-        }
-        result_links.push(link);
-        continue;
+/// Compiles `includes`/`excludes` patterns. Invalid ones are skipped unless `strict` is set.
+fn compile_patterns(
+  list: &str,
+  patterns: &[String],
+  syntax: PatternSyntax,
+  strict: bool,
+) -> std::result::Result<PatternSet, String> {
+  let mut compiled = Vec::with_capacity(patterns.len());
+  for (index, pattern) in patterns.iter().enumerate() {
+    match compile_pattern(pattern, syntax) {
+      Ok(pattern) => compiled.push(CompiledPattern { index, ..pattern }),
+      Err(e) if strict => {
+        let error = PatternError::new(list, index, pattern, e);
+        let position = error
+          .start
+          .map(|start| format!(" at offset {start}"))
+          .unwrap_or_default();
+        return Err(format!(
+          "Invalid {list} pattern #{index} '{pattern}'{position}: {}",
+          error.message
+        ));
       }
-
-      denial_reasons.insert(link, EXTERNAL_LINK.to_string());
+      Err(_) => {}
     }
   }
+  Ok(PatternSet {
+    syntax,
+    patterns: compiled,
+  })
+}
+
+fn _validate_crawl_patterns(
+  data: ValidatePatternsCall,
+) -> std::result::Result<PatternValidationResult, String> {
+  let syntax = PatternSyntax::parse(data.pattern_syntax.as_deref())?;
+  let errors: Vec<PatternError> = [(INCLUDES, &data.includes), (EXCLUDES, &data.excludes)]
+    .into_iter()
+    .flat_map(|(list, patterns)| {
+      patterns
+        .iter()
+        .enumerate()
+        .filter_map(move |(index, pattern)| {
+          compile_pattern(pattern, syntax)
+            .err()
+            .map(|e| PatternError::new(list, index, pattern, e))
+        })
+    })
+    .collect();
 
-  Ok(FilterLinksResult {
-    links: result_links,
-    denial_reasons,
+  Ok(PatternValidationResult {
+    valid: errors.is_empty(),
+    errors,
   })
 }
 
-/// Filter links based on crawling rules and constraints.
+/// Check `includes`/`excludes` patterns and report every one that fails to compile.
 #[napi]
-pub async fn filter_links(data: FilterLinksCall) -> Result<FilterLinksResult> {
-  let res = task::spawn_blocking(move || _filter_links(data))
+pub async fn validate_crawl_patterns(
+  data: ValidatePatternsCall,
+) -> Result<PatternValidationResult> {
+  let res = task::spawn_blocking(move || _validate_crawl_patterns(data))
     .await
     .map_err(|e| {
       napi::Error::new(
         napi::Status::GenericFailure,
-        format!("filter_links join error: {e}"),
+        format!("validate_crawl_patterns join error: {e}"),
       )
     })?;
 
-  res.map_err(|e| Error::new(Status::GenericFailure, format!("Filter links error: {e}")))
+  res.map_err(|e| {
+    Error::new(
+      Status::GenericFailure,
+      format!("Validate patterns error: {e}"),
+    )
+  })
 }
 
-fn _filter_url(data: FilterUrlCall) -> std::result::Result<FilterUrlResult, String> {
-  let mut full_url = data.href.clone();
-
-  // Handle relative URLs
-  if !data.href.starts_with("http") {
-    match Url::parse(&data.url) {
-      Ok(base) => match base.join(&data.href) {
-        Ok(resolved) => full_url = resolved.to_string(),
-        Err(_) => {
-          return Ok(FilterUrlResult {
-            allowed: false,
-            url: None,
-            denial_reason: Some(URL_PARSE_ERROR.to_string()),
-          });
-        }
-      },
-      Err(_) => {
-        return Ok(FilterUrlResult {
-          allowed: false,
-          url: None,
-          denial_reason: Some(URL_PARSE_ERROR.to_string()),
-        });
-      }
+const TESTED_PATH: &str = "path";
+pub(crate) const TESTED_URL: &str = "url";
+
+/// A denial code plus the evidence behind it.
+pub(crate) struct Denial {
+  pub(crate) code: &'static str,
+  pub(crate) tested: Option<(&'static str, String)>,
+  pub(crate) pattern_index: Option<usize>,
+  pub(crate) pattern: Option<String>,
+  // Boxed to keep `Result<(), Denial>` small on the common path.
+  pub(crate) robots_rule: Option<Box<RobotsRule>>,
+}
+
+impl Denial {
+  pub(crate) fn new(code: &'static str) -> Self {
+    Self {
+      code,
+      tested: None,
+      pattern_index: None,
+      pattern: None,
+      robots_rule: None,
     }
   }
 
-  let url = match Url::parse(&full_url) {
-    Ok(url) => url,
-    Err(_) => {
-      return Ok(FilterUrlResult {
-        allowed: false,
-        url: None,
-        denial_reason: Some(URL_PARSE_ERROR.to_string()),
-      });
+  pub(crate) fn tested(self, tested: &'static str, value: &str) -> Self {
+    Self {
+      tested: Some((tested, value.to_string())),
+      ..self
     }
-  };
+  }
 
-  let base_url = match Url::parse(&data.base_url) {
-    Ok(url) => url,
-    Err(_) => {
-      return Ok(FilterUrlResult {
-        allowed: false,
-        url: None,
-        denial_reason: Some(URL_PARSE_ERROR.to_string()),
-      });
+  fn pattern(code: &'static str, patterns: &PatternSet, result: PatternMatch) -> Self {
+    let pattern = result.index.map(|index| &patterns.patterns[index]);
+    Self {
+      pattern_index: pattern.map(|pattern| pattern.index),
+      pattern: pattern.map(|pattern| pattern.source.clone()),
+      ..Self::new(code).tested(result.tested, result.value)
     }
-  };
-
-  let path = url.path();
-  let url_str = url.as_str();
+  }
 
-  if is_non_web_protocol(url_str) {
-    return Ok(FilterUrlResult {
-      allowed: false,
-      url: None,
-      denial_reason: Some(NON_WEB_PROTOCOL.to_string()),
-    });
+  pub(crate) fn into_link_denial(self, url: Option<String>) -> LinkDenial {
+    let (tested, tested_value) = match self.tested {
+      Some((tested, value)) => (Some(tested.to_string()), Some(value)),
+      None => (None, None),
+    };
+    LinkDenial {
+      code: self.code.to_string(),
+      url,
+      tested,
+      tested_value,
+      pattern_index: self.pattern_index.map(|index| index as u32),
+      pattern: self.pattern,
+      robots_rule: self.robots_rule.map(|rule| *rule),
+    }
   }
+}
 
-  let excludes_regex: Vec<Regex> = data
-    .excludes
-    .iter()
-    .filter_map(|e| Regex::new(e).ok())
-    .collect();
+/// A crawl config with its URLs parsed, patterns compiled and robots.txt built.
+pub(crate) struct CompiledCrawlFilter {
+  base_url: Url,
+  initial_url: Url,
+  max_depth: u32,
+  max_discovery_depth: Option<u32>,
+  regex_on_full_url: bool,
+  excludes: PatternSet,
+  includes: PatternSet,
+  allow_backward_crawling: bool,
+  robot: Option<Robot>,
+  /// The group `robot` applies, to explain its `ROBOTS_TXT` denials.
+  robots: Option<RobotsGroupMatch>,
+  allow_external_content_links: bool,
+  allow_subdomains: bool,
+  scope: HostScope,
+  normalizer: Option<UrlNormalizer>,
+  file_extensions: HashSet<String>,
+  /// Extensions explicitly added to and removed from `file_extensions`.
+  file_extension_overrides: (HashSet<String>, HashSet<String>),
+  social_media_domains: Vec<String>,
+  allowed_categories: Option<HashSet<&'static str>>,
+  classifier: LinkClassifier,
+  scorer: Option<LinkScorer>,
+  respect_nofollow: bool,
+  fragment_policy: Option<FragmentPolicy>,
+  preferred_locales: Vec<String>,
+}
 
-  let robot = if !data.ignore_robots_txt && !data.robots_txt.is_empty() {
-    Robot::new("FireCrawlAgent", data.robots_txt.as_bytes())
-      .ok()
-      .or_else(|| Robot::new("FirecrawlAgent", data.robots_txt.as_bytes()).ok())
-  } else {
-    None
-  };
+impl CompiledCrawlFilter {
+  pub(crate) fn new(options: CrawlFilterOptions) -> std::result::Result<Self, String> {
+    let base_url =
+      Url::parse(&options.base_url).map_err(|e| format!("Base URL parse error: {e}"))?;
+    let initial_url =
+      Url::parse(&options.initial_url).map_err(|e| format!("Initial URL parse error: {e}"))?;
 
-  if is_internal_link(&url, &base_url) {
-    // INTERNAL LINKS
-    if !no_sections(url_str) {
-      return Ok(FilterUrlResult {
-        allowed: false,
-        url: None,
-        denial_reason: Some(SECTION_LINK.to_string()),
-      });
-    }
+    let syntax = PatternSyntax::parse(options.pattern_syntax.as_deref())?;
+    let strict = options.strict_patterns.unwrap_or(false);
+    let excludes = compile_patterns(EXCLUDES, &options.excludes, syntax, strict)?;
+    let includes = compile_patterns(INCLUDES, &options.includes, syntax, strict)?;
 
-    if !excludes_regex.is_empty() && excludes_regex.iter().any(|r| r.is_match(path)) {
-      return Ok(FilterUrlResult {
-        allowed: false,
-        url: None,
-        denial_reason: Some(EXCLUDE_PATTERN.to_string()),
-      });
-    }
+    let scope = HostScope::new(
+      &base_url,
+      options.host_scope.as_deref(),
+      options.allowed_hosts.as_deref(),
+    )?;
 
-    if let Some(ref robot) = robot {
-      if !robot.allowed(url_str) {
-        return Ok(FilterUrlResult {
-          allowed: false,
-          url: None,
-          denial_reason: Some(ROBOTS_TXT.to_string()),
-        });
-      }
-    }
+    let fragment_policy = FragmentPolicy::parse(
+      options.fragment_policy.as_deref(),
+      options.fragment_pattern.as_deref(),
+    )?;
 
-    Ok(FilterUrlResult {
-      allowed: true,
-      url: Some(full_url),
-      denial_reason: None,
-    })
-  } else {
-    // EXTERNAL LINKS
-    if is_social_media_or_email(url_str) {
-      return Ok(FilterUrlResult {
-        allowed: false,
-        url: None,
-        denial_reason: Some(SOCIAL_MEDIA.to_string()),
-      });
-    }
+    let scorer = options
+      .scoring
+      .as_ref()
+      .map(|scoring| LinkScorer::new(scoring, &includes));
 
-    if !excludes_regex.is_empty() && excludes_regex.iter().any(|r| r.is_match(url_str)) {
-      return Ok(FilterUrlResult {
-        allowed: false,
-        url: None,
-        denial_reason: Some(EXCLUDE_PATTERN.to_string()),
-      });
-    }
+    let allowed_categories = options
+      .allowed_categories
+      .as_deref()
+      .map(parse_link_categories)
+      .transpose()?;
 
-    let context_url = match Url::parse(&data.url) {
-      Ok(url) => url,
-      Err(_) => {
-        return Ok(FilterUrlResult {
-          allowed: false,
-          url: None,
-          denial_reason: Some(URL_PARSE_ERROR.to_string()),
-        });
+    let (robot, robots) = if !options.ignore_robots_txt && !options.robots_txt.is_empty() {
+      match build_robot(&options.robots_txt, options.robots_user_agents.as_deref()) {
+        Some((robot, group)) => (Some(robot), group),
+        None => (None, None),
       }
+    } else {
+      (None, None)
     };
 
-    if is_internal_link(&context_url, &base_url)
-      && data.allow_external_content_links
-      && !is_external_main_page(url_str)
-    {
-      return Ok(FilterUrlResult {
-        allowed: true,
-        url: Some(full_url),
-        denial_reason: None,
-      });
-    }
+    Ok(Self {
+      base_url,
+      initial_url,
+      max_depth: options.max_depth,
+      max_discovery_depth: options.max_discovery_depth,
+      regex_on_full_url: options.regex_on_full_url,
+      excludes,
+      includes,
+      allow_backward_crawling: options.allow_backward_crawling,
+      robot,
+      robots,
+      allow_external_content_links: options.allow_external_content_links,
+      allow_subdomains: options.allow_subdomains,
+      scope,
+      normalizer: options
+        .normalization
+        .as_ref()
+        .map(|normalization| UrlNormalizer {
+          fragment_policy: fragment_policy.clone(),
+          ..UrlNormalizer::new(normalization)
+        }),
+      file_extensions: apply_deny_list_override(
+        FILE_EXTENSIONS,
+        options.file_extensions.as_ref(),
+        normalize_file_extension,
+      ),
+      file_extension_overrides: deny_list_override_entries(
+        options.file_extensions.as_ref(),
+        normalize_file_extension,
+      ),
+      social_media_domains: apply_deny_list_override(
+        SOCIAL_MEDIA_DOMAINS,
+        options.social_media_domains.as_ref(),
+        str::to_string,
+      ),
+      allowed_categories,
+      classifier: LinkClassifier::new(options.content_types),
+      scorer,
+      respect_nofollow: options.respect_nofollow.unwrap_or(false),
+      fragment_policy,
+      preferred_locales: options
+        .preferred_locales
+        .iter()
+        .flatten()
+        .map(|locale| normalize_locale(locale))
+        .collect(),
+    })
+  }
 
-    if data.allow_subdomains && !is_social_media_or_email(url_str) && is_subdomain(&url, &base_url)
-    {
-      return Ok(FilterUrlResult {
-        allowed: true,
-        url: Some(full_url),
-        denial_reason: None,
-      });
+  fn is_file(&self, path: &str) -> bool {
+    path
+      .rfind('.')
+      .is_some_and(|dot_pos| self.file_extensions.contains(&path[dot_pos..]))
+  }
+
+  /// Whether `file_extensions` explicitly denies (`add`) or allows (`remove`) the extension.
+  fn file_extension_override(&self, path: &str) -> Option<bool> {
+    let extension = &path[path.rfind('.')?..];
+    let (add, remove) = &self.file_extension_overrides;
+    if add.contains(extension) {
+      Some(true)
+    } else if remove.contains(extension) {
+      Some(false)
+    } else {
+      None
     }
+  }
 
-    Ok(FilterUrlResult {
-      allowed: false,
-      url: None,
-      denial_reason: Some(EXTERNAL_LINK.to_string()),
-    })
+  fn social_media_domain(&self, url_str: &str) -> Option<&str> {
+    self
+      .social_media_domains
+      .iter()
+      .find(|domain| url_str.contains(domain.as_str()))
+      .map(String::as_str)
   }
-}
 
-/// Filter a single URL based on crawling rules and constraints.
-#[napi]
-pub async fn filter_url(data: FilterUrlCall) -> Result<FilterUrlResult> {
-  let res = task::spawn_blocking(move || _filter_url(data))
-    .await
-    .map_err(|e| {
-      napi::Error::new(
-        napi::Status::GenericFailure,
-        format!("filter_url join error: {e}"),
-      )
-    })?;
+  fn check_excludes(&self, url: &Url, full_url: bool) -> std::result::Result<(), Denial> {
+    if self.excludes.is_empty() {
+      return Ok(());
+    }
+    let result = self.excludes.test(url, full_url);
+    if result.matched {
+      return Err(Denial::pattern(EXCLUDE_PATTERN, &self.excludes, result));
+    }
+    Ok(())
+  }
 
-  res.map_err(|e| Error::new(Status::GenericFailure, format!("Filter URL error: {e}")))
-}
+  fn check_external_excludes(&self, url: &Url) -> std::result::Result<(), Denial> {
+    if self.excludes.is_empty() {
+      return Ok(());
+    }
+    let result = self.excludes.test_external(url);
+    if result.matched {
+      return Err(Denial::pattern(EXCLUDE_PATTERN, &self.excludes, result));
+    }
+    Ok(())
+  }
 
-fn _parse_sitemap_xml(xml_content: &str) -> std::result::Result<ParsedSitemap, String> {
-  let doc = roxmltree::Document::parse_with_options(
-    xml_content,
-    roxmltree::ParsingOptions {
-      allow_dtd: true,
-      ..Default::default()
-    },
-  )
-  .map_err(|e| format!("XML parsing error: {e}"))?;
-  let root = doc.root_element();
+  fn check_includes(&self, url: &Url, full_url: bool) -> std::result::Result<(), Denial> {
+    if self.includes.is_empty() {
+      return Ok(());
+    }
+    let result = self.includes.test(url, full_url);
+    if !result.matched {
+      return Err(Denial::pattern(INCLUDE_PATTERN, &self.includes, result));
+    }
+    Ok(())
+  }
 
-  match root.tag_name().name() {
-    "sitemapindex" => {
-      let sitemaps = root
-        .children()
-        .filter(|n| n.is_element() && n.tag_name().name() == "sitemap")
-        .filter_map(|sitemap_node| {
-          sitemap_node
-            .children()
-            .find(|n| n.is_element() && n.tag_name().name() == "loc")
-            .and_then(|loc_node| loc_node.text())
-            .map(|loc_text| SitemapEntry {
-              loc: vec![loc_text.to_string()],
-            })
-        })
-        .collect();
+  fn robots_user_agent(&self) -> Option<String> {
+    self.robots.as_ref().map(|robots| robots.user_agent.clone())
+  }
 
-      Ok(ParsedSitemap {
-        urlset: None,
-        sitemapindex: Some(SitemapIndex { sitemap: sitemaps }),
-      })
-    }
-    "urlset" => {
-      let urls = root
-        .children()
-        .filter(|n| n.is_element() && n.tag_name().name() == "url")
-        .filter_map(|url_node| {
-          url_node
-            .children()
-            .find(|n| n.is_element() && n.tag_name().name() == "loc")
-            .and_then(|loc_node| loc_node.text())
-            .map(|loc_text| SitemapUrl {
-              loc: vec![loc_text.to_string()],
-            })
+  fn check_robots(&self, url: &Url) -> std::result::Result<(), Denial> {
+    match &self.robot {
+      Some(robot) if !robot.allowed(url.as_str()) => {
+        let path = robots_path(url.as_str());
+        // texting_robots doesn't say which line matched, so ask our own parser.
+        let rule = self
+          .robots
+          .as_ref()
+          .and_then(|robots| robots.decide(&path).1)
+          .filter(|rule| rule.directive == "disallow");
+        Err(Denial {
+          robots_rule: rule.cloned().map(Box::new),
+          ..Denial::new(ROBOTS_TXT).tested(TESTED_PATH, &path)
         })
-        .collect();
-
-      Ok(ParsedSitemap {
-        urlset: Some(SitemapUrlset { url: urls }),
-        sitemapindex: None,
-      })
+      }
+      _ => Ok(()),
     }
-    _ => Err("Invalid sitemap format: root element must be 'sitemapindex' or 'urlset'".to_string()),
   }
-}
 
-/// Parse XML sitemap content into structured data.
-#[napi]
-pub async fn parse_sitemap_xml(xml_content: String) -> Result<ParsedSitemap> {
-  let res = task::spawn_blocking(move || _parse_sitemap_xml(&xml_content))
-    .await
-    .map_err(|e| {
-      napi::Error::new(
-        napi::Status::GenericFailure,
-        format!("parse_sitemap_xml join error: {e}"),
-      )
-    })?;
+  /// Resolves `link` against the base URL and applies the fragment policy. Also returns the
+  /// key links are deduplicated by: the normalized URL when normalization is on.
+  pub(crate) fn resolve(&self, link: &str) -> Option<(Url, String)> {
+    let mut url = self.base_url.join(link).ok()?;
+    if let Some(policy) = &self.fragment_policy {
+      policy.apply(&mut url);
+    }
 
-  res.map_err(|e| {
-    Error::new(
-      Status::GenericFailure,
-      format!("Parse sitemap XML error: {e}"),
-    )
-  })
-}
+    let normalized = match &self.normalizer {
+      Some(normalizer) => normalizer.normalize(url.clone()),
+      None => url.to_string(),
+    };
+    Some((url, normalized))
+  }
 
-fn _process_sitemap(xml_content: &str) -> std::result::Result<SitemapProcessingResult, String> {
-  let parsed = _parse_sitemap_xml(xml_content)?;
-  let mut instructions = Vec::new();
-  let mut total_count: u32 = 0;
+  /// Maps each alternate in `page.locale_alternates` to the dedupe key and URL of the
+  /// alternate kept for its group, chosen among those of `links` that pass `check_link`.
+  fn preferred_alternates(
+    &self,
+    links: &[String],
+    page: &FilterLinksPage,
+  ) -> HashMap<String, (String, String)> {
+    let mut preferred_alternates = HashMap::new();
+    if self.preferred_locales.is_empty() {
+      return preferred_alternates;
+    }
 
-  if let Some(sitemapindex) = parsed.sitemapindex {
-    let sitemap_urls: Vec<String> = sitemapindex
-      .sitemap
+    let candidates: HashSet<String> = links
       .iter()
-      .filter_map(|sitemap| {
-        if !sitemap.loc.is_empty() {
-          Some(sitemap.loc[0].trim().to_string())
-        } else {
-          None
-        }
+      .filter_map(|link| {
+        let (url, key) = self.resolve(link)?;
+        let hint = page.link_hints.as_ref().and_then(|hints| hints.get(link));
+        self.check_link(&url, hint).is_ok().then_some(key)
       })
       .collect();
 
-    if !sitemap_urls.is_empty() {
-      let count = sitemap_urls.len() as u32;
-      instructions.push(SitemapInstruction {
-        action: "recurse".to_string(),
-        urls: sitemap_urls,
-        count,
-      });
-      total_count += count;
+    for group in page.locale_alternates.iter().flatten() {
+      let linked: Vec<_> = group
+        .iter()
+        .filter(|alternate| {
+          self
+            .resolve(&alternate.href)
+            .is_some_and(|(_, key)| candidates.contains(&key))
+        })
+        .collect();
+      let Some((url, key)) = preferred_alternate(&linked, &self.preferred_locales)
+        .and_then(|preferred| self.resolve(&preferred.href))
+      else {
+        continue;
+      };
+      for alternate in group {
+        if let Some((_, alternate_key)) = self.resolve(&alternate.href) {
+          preferred_alternates.insert(alternate_key, (key.clone(), url.to_string()));
+        }
+      }
     }
-  } else if let Some(urlset) = parsed.urlset {
-    let mut xml_sitemaps = Vec::new();
-    let mut valid_urls = Vec::new();
+    preferred_alternates
+  }
 
-    for url_entry in urlset.url {
-      if !url_entry.loc.is_empty() {
-        let url = url_entry.loc[0].trim();
-        let url_lower = url.to_lowercase();
-        if url_lower.ends_with(".xml") || url_lower.ends_with(".xml.gz") {
-          xml_sitemaps.push(url.to_string());
-        } else if let Ok(parsed_url) = Url::parse(url) {
-          let path_lower = parsed_url.path().to_lowercase();
-          if !is_file(&path_lower) {
-            valid_urls.push(url.to_string());
+  pub(crate) fn filter_links(
+    &self,
+    links: Vec<String>,
+    limit: Option<i64>,
+    page: &FilterLinksPage,
+  ) -> FilterLinksResult {
+    let limit = limit.map_or(usize::MAX, |x| x.max(0) as usize);
+    let mut result_links = Vec::new();
+    let mut denial_reasons = HashMap::new();
+    let mut denials = HashMap::new();
+    let mut accepted = HashSet::new();
+
+    let link_depth = page.parent_depth.map(|depth| depth.saturating_add(1));
+    let beyond_discovery_depth = match (link_depth, self.max_discovery_depth) {
+      (Some(depth), Some(max_depth)) => depth > max_depth,
+      _ => false,
+    };
+    let page_nofollow = self.respect_nofollow && page.nofollow.unwrap_or(false);
+
+    let mut scores = self.scorer.as_ref().map(|_| HashMap::new());
+    let preferred_alternates = self.preferred_alternates(&links, page);
+
+    for link in links {
+      // Scoring has to see every link before it can keep the best ones.
+      if scores.is_none() && result_links.len() >= limit {
+        break;
+      }
+
+      let Some((url, normalized)) = self.resolve(&link) else {
+        denial_reasons.insert(link.clone(), URL_PARSE_ERROR.to_string());
+        denials.insert(link, Denial::new(URL_PARSE_ERROR).into_link_denial(None));
+        continue;
+      };
+
+      let hint = page.link_hints.as_ref().and_then(|hints| hints.get(&link));
+      let verdict = if beyond_discovery_depth {
+        Err(Denial::new(DISCOVERY_DEPTH_LIMIT))
+      } else if page_nofollow {
+        Err(Denial::new(NOFOLLOW))
+      } else {
+        self.check_link(&url, hint).and_then(|()| {
+          if let Some(preferred) = preferred_alternates
+            .get(&normalized)
+            .filter(|(key, _)| *key != normalized)
+          {
+            return Err(Denial {
+              pattern: Some(preferred.1.clone()),
+              ..Denial::new(LOCALE_ALTERNATE).tested(TESTED_URL, &normalized)
+            });
+          }
+
+          let dedupe = self.normalizer.is_some() || self.fragment_policy.is_some();
+          if dedupe && !accepted.insert(normalized.clone()) {
+            Err(Denial::new(DUPLICATE).tested(TESTED_URL, &normalized))
+          } else {
+            Ok(())
           }
+        })
+      };
+
+      match verdict {
+        Ok(()) => {
+          // With a fragment policy the rewritten URL is what should be crawled.
+          let link = match self.fragment_policy {
+            Some(_) => url.to_string(),
+            None => link,
+          };
+          if let (Some(scorer), Some(scores)) = (&self.scorer, &mut scores) {
+            let include = self.includes.test(&url, self.regex_on_full_url).index;
+            scores.insert(link.clone(), scorer.score(&url, hint, include));
+          }
+          result_links.push(link);
+        }
+        Err(denial) => {
+          denial_reasons.insert(link.clone(), denial.code.to_string());
+          denials.insert(link, denial.into_link_denial(Some(normalized)));
         }
       }
     }
 
-    if !xml_sitemaps.is_empty() {
-      let count = xml_sitemaps.len() as u32;
-      instructions.push(SitemapInstruction {
-        action: "recurse".to_string(),
-        urls: xml_sitemaps,
-        count,
-      });
-      total_count += count;
+    if let Some(all_scores) = scores.take() {
+      // Stable, so equal scores keep their input order.
+      result_links.sort_by(|a, b| all_scores[b].total_cmp(&all_scores[a]));
+      result_links.truncate(limit);
+      scores = Some(
+        result_links
+          .iter()
+          .map(|link| (link.clone(), all_scores[link]))
+          .collect(),
+      );
     }
 
-    if !valid_urls.is_empty() {
-      let count = valid_urls.len() as u32;
-      instructions.push(SitemapInstruction {
-        action: "process".to_string(),
-        urls: valid_urls,
-        count,
-      });
-      total_count += count;
+    FilterLinksResult {
+      links: result_links,
+      denial_reasons,
+      denials,
+      robots_user_agent: self.robots_user_agent(),
+      link_depth,
+      scores,
     }
   }
 
-  Ok(SitemapProcessingResult {
-    instructions,
-    total_count,
-  })
-}
+  /// Applies the crawl rules to a resolved link, returning why it is rejected.
+  fn check_link(&self, url: &Url, hint: Option<&LinkHint>) -> std::result::Result<(), Denial> {
+    let path = url.path();
+    let url_str = url.as_str();
 
-/// Process sitemap XML and extract crawling instructions.
-#[napi]
-pub async fn process_sitemap(xml_content: String) -> Result<SitemapProcessingResult> {
-  let res = task::spawn_blocking(move || _process_sitemap(&xml_content))
-    .await
-    .map_err(|e| {
-      napi::Error::new(
-        napi::Status::GenericFailure,
-        format!("process_sitemap join error: {e}"),
-      )
-    })?;
+    if is_non_web_protocol(url_str) {
+      return Err(Denial::new(NON_WEB_PROTOCOL).tested(TESTED_URL, url_str));
+    }
 
-  res.map_err(|e| Error::new(Status::GenericFailure, format!("Parse sitemap error: {e}")))
-}
+    let rel_nofollow = hint.and_then(|hint| hint.rel.as_ref()).is_some_and(|rel| {
+      rel
+        .iter()
+        .any(|token| token.eq_ignore_ascii_case("nofollow"))
+    });
+    if self.respect_nofollow && rel_nofollow {
+      return Err(Denial::new(NOFOLLOW));
+    }
 
-#[cfg(test)]
-mod tests {
-  use super::*;
+    if get_url_depth(path) > self.max_depth {
+      return Err(Denial::new(DEPTH_LIMIT).tested(TESTED_PATH, path));
+    }
 
-  #[test]
-  fn test_parse_sitemap_xml_urlset() {
-    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
-<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
-  <url>
-    <loc>https://example.com/page1</loc>
-  </url>
-  <url>
-    <loc>https://example.com/page2</loc>
-  </url>
-</urlset>"#;
+    let denied_file = match &self.allowed_categories {
+      Some(allowed) => self
+        .file_extension_override(path)
+        .unwrap_or_else(|| !allowed.contains(self.classifier.classify(url, hint).0)),
+      None => self.is_file(path),
+    };
+    if denied_file {
+      return Err(Denial::new(FILE_TYPE).tested(TESTED_PATH, path));
+    }
 
-    let result = _parse_sitemap_xml(xml_content).unwrap();
-    assert!(result.urlset.is_some());
-    let urlset = result.urlset.unwrap();
-    assert_eq!(urlset.url.len(), 2);
-    assert_eq!(urlset.url[0].loc[0], "https://example.com/page1");
-    assert_eq!(urlset.url[1].loc[0], "https://example.com/page2");
+    if self.scope.is_internal(url) {
+      // INTERNAL LINKS
+      if self.fragment_policy.is_none() && !no_sections(url_str) {
+        return Err(Denial::new(SECTION_LINK).tested(TESTED_URL, url_str));
+      }
+
+      if !self.allow_backward_crawling && !path.starts_with(self.initial_url.path()) {
+        return Err(Denial::new(BACKWARD_CRAWLING).tested(TESTED_PATH, path));
+      }
+
+      self.check_excludes(url, self.regex_on_full_url)?;
+      self.check_includes(url, self.regex_on_full_url)?;
+      self.check_robots(url)
+    } else {
+      // EXTERNAL LINKS
+      if let Some(domain) = self.social_media_domain(url_str) {
+        return Err(Denial {
+          pattern: Some(domain.to_string()),
+          ..Denial::new(SOCIAL_MEDIA).tested(TESTED_URL, url_str)
+        });
+      }
+
+      self.check_external_excludes(url)?;
+
+      if self.scope.is_internal(&self.initial_url)
+        && self.allow_external_content_links
+        && !is_external_main_page(url_str)
+      {
+        return Ok(());
+      }
+
+      if self.allow_subdomains && self.scope.is_subdomain(url) {
+        // When allowing subdomains, still honor include patterns
+        return self.check_includes(url, self.regex_on_full_url);
+      }
+
+      Err(Denial::new(EXTERNAL_LINK).tested(TESTED_URL, url_str))
+    }
   }
 
-  #[test]
-  fn test_parse_sitemap_xml_sitemapindex() {
-    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
-<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
-  <sitemap>
-    <loc>https://example.com/sitemap1.xml</loc>
-  </sitemap>
-  <sitemap>
-    <loc>https://example.com/sitemap2.xml</loc>
-  </sitemap>
-</sitemapindex>"#;
+  /// Decides whether `href`, found on the page at `context`, should be followed.
+  fn filter_url(&self, href: &str, context: &str) -> FilterUrlResult {
+    let mut full_url = href.to_string();
 
-    let result = _parse_sitemap_xml(xml_content).unwrap();
+    // Handle relative URLs
+    if !href.starts_with("http") {
+      match Url::parse(context) {
+        Ok(base) => match base.join(href) {
+          Ok(resolved) => full_url = resolved.to_string(),
+          Err(_) => return FilterUrlResult::denied(Denial::new(URL_PARSE_ERROR), None),
+        },
+        Err(_) => return FilterUrlResult::denied(Denial::new(URL_PARSE_ERROR), None),
+      }
+    }
+
+    let mut url = match Url::parse(&full_url) {
+      Ok(url) => url,
+      Err(_) => return FilterUrlResult::denied(Denial::new(URL_PARSE_ERROR), None),
+    };
+    if let Some(policy) = &self.fragment_policy {
+      policy.apply(&mut url);
+      full_url = url.to_string();
+    }
+
+    let internal = self.scope.is_internal(&url);
+    let result = match self.check_url(&url, internal, context) {
+      Ok(()) => FilterUrlResult::allowed(full_url),
+      Err(denial) => FilterUrlResult::denied(denial, Some(url.to_string())),
+    };
+
+    if internal {
+      FilterUrlResult {
+        robots_user_agent: self.robots_user_agent(),
+        ..result
+      }
+    } else {
+      result
+    }
+  }
+
+  fn check_url(&self, url: &Url, internal: bool, context: &str) -> std::result::Result<(), Denial> {
+    let url_str = url.as_str();
+
+    if is_non_web_protocol(url_str) {
+      return Err(Denial::new(NON_WEB_PROTOCOL).tested(TESTED_URL, url_str));
+    }
+
+    if internal {
+      // INTERNAL LINKS
+      if self.fragment_policy.is_none() && !no_sections(url_str) {
+        return Err(Denial::new(SECTION_LINK).tested(TESTED_URL, url_str));
+      }
+
+      self.check_excludes(url, false)?;
+      self.check_robots(url)
+    } else {
+      // EXTERNAL LINKS
+      if let Some(domain) = self.social_media_domain(url_str) {
+        return Err(Denial {
+          pattern: Some(domain.to_string()),
+          ..Denial::new(SOCIAL_MEDIA).tested(TESTED_URL, url_str)
+        });
+      }
+
+      self.check_external_excludes(url)?;
+
+      let context_url = Url::parse(context).map_err(|_| Denial::new(URL_PARSE_ERROR))?;
+
+      if self.scope.is_internal(&context_url)
+        && self.allow_external_content_links
+        && !is_external_main_page(url_str)
+      {
+        return Ok(());
+      }
+
+      if self.allow_subdomains && self.scope.is_subdomain(url) {
+        return Ok(());
+      }
+
+      Err(Denial::new(EXTERNAL_LINK).tested(TESTED_URL, url_str))
+    }
+  }
+}
+
+pub(crate) fn _filter_links(
+  data: FilterLinksCall,
+) -> std::result::Result<FilterLinksResult, String> {
+  let page = data.page.unwrap_or_default();
+  if data.options.max_discovery_depth.is_some() && page.parent_depth.is_none() {
+    return Err("max_discovery_depth requires parent_depth".to_string());
+  }
+
+  if data.limit.is_some_and(|limit| limit <= 0) {
+    return Ok(FilterLinksResult {
+      links: Vec::new(),
+      denial_reasons: HashMap::new(),
+      denials: HashMap::new(),
+      robots_user_agent: None,
+      link_depth: None,
+      scores: None,
+    });
+  }
+
+  let filter = CompiledCrawlFilter::new(data.options)?;
+  Ok(filter.filter_links(data.links, data.limit, &page))
+}
+
+/// Filter links based on crawling rules and constraints.
+#[napi]
+pub async fn filter_links(data: FilterLinksCall) -> Result<FilterLinksResult> {
+  let res = task::spawn_blocking(move || _filter_links(data))
+    .await
+    .map_err(|e| {
+      napi::Error::new(
+        napi::Status::GenericFailure,
+        format!("filter_links join error: {e}"),
+      )
+    })?;
+
+  res.map_err(|e| Error::new(Status::GenericFailure, format!("Filter links error: {e}")))
+}
+
+fn _filter_url(data: FilterUrlCall) -> std::result::Result<FilterUrlResult, String> {
+  if Url::parse(&data.options.base_url).is_err() {
+    return Ok(FilterUrlResult::denied(Denial::new(URL_PARSE_ERROR), None));
+  }
+
+  let filter = CompiledCrawlFilter::new(data.options)?;
+  Ok(filter.filter_url(&data.href, &data.url))
+}
+
+/// Filter a single URL based on crawling rules and constraints.
+#[napi]
+pub async fn filter_url(data: FilterUrlCall) -> Result<FilterUrlResult> {
+  let res = task::spawn_blocking(move || _filter_url(data))
+    .await
+    .map_err(|e| {
+      napi::Error::new(
+        napi::Status::GenericFailure,
+        format!("filter_url join error: {e}"),
+      )
+    })?;
+
+  res.map_err(|e| Error::new(Status::GenericFailure, format!("Filter URL error: {e}")))
+}
+
+/// A crawl filter compiled once from the crawl config and reused for every page of the crawl.
+#[napi]
+pub struct CrawlFilter {
+  inner: Arc<CompiledCrawlFilter>,
+}
+
+#[napi]
+impl CrawlFilter {
+  #[napi(constructor)]
+  pub fn new(options: CrawlFilterOptions) -> Result<Self> {
+    let inner = CompiledCrawlFilter::new(options)
+      .map_err(|e| Error::new(Status::GenericFailure, format!("Crawl filter error: {e}")))?;
+    Ok(Self {
+      inner: Arc::new(inner),
+    })
+  }
+
+  /// Filter links found on `page` based on crawling rules and constraints.
+  #[napi]
+  pub async fn filter_links(
+    &self,
+    links: Vec<String>,
+    limit: Option<i64>,
+    page: Option<FilterLinksPage>,
+  ) -> Result<FilterLinksResult> {
+    let inner = Arc::clone(&self.inner);
+    task::spawn_blocking(move || inner.filter_links(links, limit, &page.unwrap_or_default()))
+      .await
+      .map_err(|e| {
+        napi::Error::new(
+          napi::Status::GenericFailure,
+          format!("filter_links join error: {e}"),
+        )
+      })
+  }
+
+  /// Filter a single URL found on the page at `context`.
+  #[napi]
+  pub async fn filter_url(&self, href: String, context: String) -> Result<FilterUrlResult> {
+    let inner = Arc::clone(&self.inner);
+    task::spawn_blocking(move || inner.filter_url(&href, &context))
+      .await
+      .map_err(|e| {
+        napi::Error::new(
+          napi::Status::GenericFailure,
+          format!("filter_url join error: {e}"),
+        )
+      })
+  }
+}
+
+/// Lowercases a language tag and uses `-` as the separator, so `en_US` matches `en-US`.
+fn normalize_locale(locale: &str) -> String {
+  locale.trim().replace('_', "-").to_lowercase()
+}
+
+/// Picks the alternate to keep from a group: the first preferred locale with an exact
+/// `hreflang` match, else one matching on language alone, else `x-default`.
+fn preferred_alternate<'a>(
+  alternates: &[&'a SitemapAlternate],
+  preferred_locales: &[String],
+) -> Option<&'a SitemapAlternate> {
+  let hreflang = |alternate: &SitemapAlternate| normalize_locale(&alternate.hreflang);
+  preferred_locales
+    .iter()
+    .find_map(|locale| {
+      let language = locale.split('-').next().unwrap_or(locale);
+      alternates
+        .iter()
+        .find(|alternate| hreflang(alternate) == *locale)
+        .or_else(|| {
+          alternates
+            .iter()
+            .find(|alternate| hreflang(alternate).split('-').next() == Some(language))
+        })
+    })
+    .or_else(|| {
+      alternates
+        .iter()
+        .find(|alternate| hreflang(alternate) == "x-default")
+    })
+    .copied()
+}
+
+/// Page-level robots directives from `<meta name="robots">` tags and `X-Robots-Tag` headers.
+#[derive(Serialize, Debug, Default, PartialEq)]
+#[napi(object)]
+pub struct PageRobotsDirectives {
+  /// Don't index the page. Also set by `none`.
+  pub noindex: bool,
+  /// Don't follow links on the page. Also set by `none`.
+  pub nofollow: bool,
+  /// Don't keep a cached copy of the page.
+  pub noarchive: bool,
+}
+
+/// Known page-level directives, including those that take a value after a colon.
+const ROBOTS_DIRECTIVES: &[&str] = &[
+  "all",
+  "noindex",
+  "nofollow",
+  "none",
+  "noarchive",
+  "nocache",
+  "nosnippet",
+  "indexifembedded",
+  "notranslate",
+  "noimageindex",
+  "unavailable_after",
+  "max-snippet",
+  "max-image-preview",
+  "max-video-preview",
+];
+
+/// Whether `token`, up to any `:value`, is a known directive.
+fn is_robots_directive(token: &str) -> bool {
+  let name = token.split(':').next().unwrap_or_default().trim();
+  ROBOTS_DIRECTIVES
+    .iter()
+    .any(|directive| directive.eq_ignore_ascii_case(name))
+}
+
+impl PageRobotsDirectives {
+  /// Applies a comma-separated directive list such as `noindex, nofollow`.
+  fn apply(&mut self, value: &str) {
+    for directive in value.split(',') {
+      match directive.trim().to_ascii_lowercase().as_str() {
+        "noindex" => self.noindex = true,
+        "nofollow" => self.nofollow = true,
+        "noarchive" => self.noarchive = true,
+        "none" => {
+          self.noindex = true;
+          self.nofollow = true;
+        }
+        _ => {}
+      }
+    }
+  }
+}
+
+/// Combines robots `<meta>` tags, given as `(name, content)` pairs, with `X-Robots-Tag` header
+/// values. Meta tags named `robots` or after one of `user_agents` apply, as do headers without
+/// an agent prefix or prefixed with one of `user_agents`, e.g. `FirecrawlAgent: noindex`.
+pub(crate) fn robots_directives(
+  meta: &[(String, String)],
+  x_robots_tags: &[String],
+  user_agents: Option<&[String]>,
+) -> PageRobotsDirectives {
+  let agents: Vec<&str> = match user_agents {
+    Some(agents) if !agents.is_empty() => agents.iter().map(String::as_str).collect(),
+    _ => DEFAULT_ROBOTS_USER_AGENTS.to_vec(),
+  };
+  let applies = |name: &str| {
+    let name = name.trim();
+    name == "*"
+      || name.eq_ignore_ascii_case("robots")
+      || agents.iter().any(|a| a.eq_ignore_ascii_case(name))
+  };
+
+  let mut directives = PageRobotsDirectives::default();
+  for (name, content) in meta {
+    if applies(name) {
+      directives.apply(content);
+    }
+  }
+  for value in x_robots_tags {
+    // An `agent:` prefix scopes the directives after it, up to the next prefix. A `key:`
+    // only reads as one when a known directive follows or it names an agent we match, so
+    // an unknown `key:value` directive doesn't swallow the rest of the header.
+    let mut agent: Option<&str> = None;
+    for token in value.split(',') {
+      let mut directive = token;
+      if let Some((prefix, rest)) = token.split_once(':') {
+        if !is_robots_directive(prefix) && (is_robots_directive(rest) || applies(prefix)) {
+          agent = Some(prefix);
+          directive = rest;
+        }
+      }
+      if agent.is_none_or(applies) {
+        directives.apply(directive);
+      }
+    }
+  }
+  directives
+}
+
+const SITEMAP_CHANGEFREQS: &[&str] = &[
+  "always", "hourly", "daily", "weekly", "monthly", "yearly", "never",
+];
+
+// Limits from the sitemaps.org protocol, enforced by `process_sitemap_bytes`.
+const SITEMAP_MAX_URLS: u32 = 50_000;
+const SITEMAP_MAX_BYTES: u32 = 50 * 1024 * 1024;
+
+const SITEMAP_TRUNCATED_MAX_URLS: &str = "MAX_URLS";
+const SITEMAP_TRUNCATED_MAX_BYTES: &str = "MAX_BYTES";
+
+const SITEMAP_FORMAT_XML: &str = "xml";
+const SITEMAP_FORMAT_TEXT: &str = "text";
+const SITEMAP_FORMAT_RSS: &str = "rss";
+const SITEMAP_FORMAT_ATOM: &str = "atom";
+
+const INVALID_SITEMAP_ROOT: &str =
+  "Invalid sitemap format: root element must be 'sitemapindex', 'urlset', 'rss' or 'feed'";
+
+#[inline]
+fn is_element_named(node: &roxmltree::Node, name: &str) -> bool {
+  node.is_element() && node.tag_name().name() == name
+}
+
+/// Trimmed, non-empty text of a leaf element.
+fn element_text<'a>(node: &roxmltree::Node<'a, '_>) -> Option<&'a str> {
+  node
+    .text()
+    .map(|text| text.trim())
+    .filter(|text| !text.is_empty())
+}
+
+/// Calls `apply` with the name and text of every leaf element below `node`.
+fn for_each_leaf(node: &roxmltree::Node, mut apply: impl FnMut(&str, &str)) {
+  for child in node.descendants().skip(1).filter(|n| n.is_element()) {
+    if child.children().any(|n| n.is_element()) {
+      continue;
+    }
+    if let Some(text) = element_text(&child) {
+      apply(child.tag_name().name(), text);
+    }
+  }
+}
+
+// The `apply_*_field` setters are shared by the DOM and the streaming parser. They take
+// an element's local name and its trimmed, non-empty text.
+
+fn apply_sitemap_url_field(entry: &mut SitemapUrl, field: &str, value: &str) {
+  match field {
+    "loc" if entry.loc.is_empty() => entry.loc.push(value.to_string()),
+    "lastmod" => entry.lastmod = Some(value.to_string()),
+    "changefreq" => {
+      entry.changefreq =
+        Some(value.to_lowercase()).filter(|c| SITEMAP_CHANGEFREQS.contains(&c.as_str()))
+    }
+    "priority" => {
+      entry.priority = value
+        .parse::<f64>()
+        .ok()
+        .filter(|p| (0.0..=1.0).contains(p))
+    }
+    _ => {}
+  }
+}
+
+fn apply_sitemap_image_field(image: &mut SitemapImage, field: &str, value: &str) {
+  match field {
+    "loc" => image.loc = value.to_string(),
+    "title" => image.title = Some(value.to_string()),
+    "caption" => image.caption = Some(value.to_string()),
+    "geo_location" => image.geo_location = Some(value.to_string()),
+    "license" => image.license = Some(value.to_string()),
+    _ => {}
+  }
+}
+
+fn apply_sitemap_news_field(news: &mut SitemapNews, field: &str, value: &str) {
+  match field {
+    // <news:publication> children
+    "name" => news.publication_name = Some(value.to_string()),
+    "language" => news.publication_language = Some(value.to_string()),
+    "publication_date" => news.publication_date = Some(value.to_string()),
+    "title" => news.title = Some(value.to_string()),
+    "keywords" => news.keywords = Some(value.to_string()),
+    _ => {}
+  }
+}
+
+fn apply_sitemap_video_field(video: &mut SitemapVideo, field: &str, value: &str) {
+  match field {
+    "thumbnail_loc" => video.thumbnail_loc = Some(value.to_string()),
+    "title" => video.title = Some(value.to_string()),
+    "description" => video.description = Some(value.to_string()),
+    "content_loc" => video.content_loc = Some(value.to_string()),
+    "player_loc" => video.player_loc = Some(value.to_string()),
+    "duration" => video.duration = value.parse().ok(),
+    "publication_date" => video.publication_date = Some(value.to_string()),
+    "family_friendly" => video.family_friendly = Some(value.eq_ignore_ascii_case("yes")),
+    _ => {}
+  }
+}
+
+fn sitemap_alternate(
+  rel: Option<&str>,
+  hreflang: Option<&str>,
+  href: Option<&str>,
+) -> Option<SitemapAlternate> {
+  if rel != Some("alternate") {
+    return None;
+  }
+
+  Some(SitemapAlternate {
+    hreflang: hreflang?.trim().to_string(),
+    href: href?.trim().to_string(),
+  })
+}
+
+/// Converts an RSS `pubDate` (RFC 2822) to the W3C format used by `<lastmod>`.
+fn feed_date_to_lastmod(value: &str) -> String {
+  DateTime::parse_from_rfc2822(value)
+    .map(|dt| dt.to_rfc3339())
+    .unwrap_or_else(|_| value.to_string())
+}
+
+/// Setter for RSS `<item>` and Atom `<entry>` children, mapped onto a sitemap entry.
+fn apply_feed_entry_field(entry: &mut SitemapUrl, field: &str, value: &str) {
+  match field {
+    "link" => entry.loc = vec![value.to_string()],
+    // Permalink fallbacks, only used when there is no <link>.
+    "guid" | "id" if entry.loc.is_empty() && value.starts_with("http") => {
+      entry.loc.push(value.to_string())
+    }
+    "updated" | "modified" => entry.lastmod = Some(feed_date_to_lastmod(value)),
+    "pubDate" | "published" | "date" if entry.lastmod.is_none() => {
+      entry.lastmod = Some(feed_date_to_lastmod(value))
+    }
+    _ => {}
+  }
+}
+
+/// Atom `<link>` elements point to the entry page when `rel` is missing or `alternate`.
+#[inline]
+fn is_atom_alternate_link(rel: Option<&str>) -> bool {
+  matches!(rel, None | Some("alternate"))
+}
+
+fn parse_feed_entry(node: &roxmltree::Node) -> Option<SitemapUrl> {
+  let mut entry = SitemapUrl::default();
+
+  for child in node.children().filter(|n| n.is_element()) {
+    let name = child.tag_name().name();
+    if name == "link" && child.has_attribute("href") {
+      if is_atom_alternate_link(child.attribute("rel")) {
+        apply_feed_entry_field(&mut entry, name, child.attribute("href")?.trim());
+      }
+    } else if let Some(text) = element_text(&child) {
+      apply_feed_entry_field(&mut entry, name, text);
+    }
+  }
+
+  if entry.loc.is_empty() {
+    None
+  } else {
+    Some(entry)
+  }
+}
+
+fn parse_sitemap_url(url_node: &roxmltree::Node) -> Option<SitemapUrl> {
+  let mut entry = SitemapUrl::default();
+
+  for child in url_node.children().filter(|n| n.is_element()) {
+    match child.tag_name().name() {
+      "image" => {
+        let mut image = SitemapImage::default();
+        for_each_leaf(&child, |field, value| {
+          apply_sitemap_image_field(&mut image, field, value)
+        });
+        if !image.loc.is_empty() {
+          entry.images.push(image);
+        }
+      }
+      "news" => {
+        let mut news = SitemapNews::default();
+        for_each_leaf(&child, |field, value| {
+          apply_sitemap_news_field(&mut news, field, value)
+        });
+        entry.news = Some(news);
+      }
+      "video" => {
+        let mut video = SitemapVideo::default();
+        for_each_leaf(&child, |field, value| {
+          apply_sitemap_video_field(&mut video, field, value)
+        });
+        entry.videos.push(video);
+      }
+      "link" => entry.alternates.extend(sitemap_alternate(
+        child.attribute("rel"),
+        child.attribute("hreflang"),
+        child.attribute("href"),
+      )),
+      name => {
+        if let Some(text) = element_text(&child) {
+          apply_sitemap_url_field(&mut entry, name, text);
+        }
+      }
+    }
+  }
+
+  if entry.loc.is_empty() {
+    None
+  } else {
+    Some(entry)
+  }
+}
+
+fn _parse_sitemap_xml(xml_content: &str) -> std::result::Result<ParsedSitemap, String> {
+  let doc = roxmltree::Document::parse_with_options(
+    xml_content,
+    roxmltree::ParsingOptions {
+      allow_dtd: true,
+      ..Default::default()
+    },
+  )
+  .map_err(|e| format!("XML parsing error: {e}"))?;
+  let root = doc.root_element();
+
+  match root.tag_name().name() {
+    "sitemapindex" => {
+      let sitemaps = root
+        .children()
+        .filter(|n| is_element_named(n, "sitemap"))
+        .filter_map(|sitemap_node| {
+          let mut entry = SitemapUrl::default();
+          for child in sitemap_node.children().filter(|n| n.is_element()) {
+            if let Some(text) = element_text(&child) {
+              apply_sitemap_url_field(&mut entry, child.tag_name().name(), text);
+            }
+          }
+          if entry.loc.is_empty() {
+            None
+          } else {
+            Some(SitemapEntry {
+              loc: entry.loc,
+              lastmod: entry.lastmod,
+            })
+          }
+        })
+        .collect();
+
+      Ok(ParsedSitemap {
+        urlset: None,
+        sitemapindex: Some(SitemapIndex { sitemap: sitemaps }),
+        format: SITEMAP_FORMAT_XML.to_string(),
+      })
+    }
+    "urlset" => {
+      let urls = root
+        .children()
+        .filter(|n| is_element_named(n, "url"))
+        .filter_map(|url_node| parse_sitemap_url(&url_node))
+        .collect();
+
+      Ok(ParsedSitemap {
+        urlset: Some(SitemapUrlset { url: urls }),
+        sitemapindex: None,
+        format: SITEMAP_FORMAT_XML.to_string(),
+      })
+    }
+    "rss" => {
+      let urls = root
+        .children()
+        .filter(|n| is_element_named(n, "channel"))
+        .flat_map(|channel| channel.children())
+        .filter(|n| is_element_named(n, "item"))
+        .filter_map(|item| parse_feed_entry(&item))
+        .collect();
+
+      Ok(ParsedSitemap {
+        urlset: Some(SitemapUrlset { url: urls }),
+        sitemapindex: None,
+        format: SITEMAP_FORMAT_RSS.to_string(),
+      })
+    }
+    "feed" => {
+      let urls = root
+        .children()
+        .filter(|n| is_element_named(n, "entry"))
+        .filter_map(|entry| parse_feed_entry(&entry))
+        .collect();
+
+      Ok(ParsedSitemap {
+        urlset: Some(SitemapUrlset { url: urls }),
+        sitemapindex: None,
+        format: SITEMAP_FORMAT_ATOM.to_string(),
+      })
+    }
+    _ => Err(INVALID_SITEMAP_ROOT.to_string()),
+  }
+}
+
+/// Parse XML sitemap content into structured data.
+#[napi]
+pub async fn parse_sitemap_xml(xml_content: String) -> Result<ParsedSitemap> {
+  let res = task::spawn_blocking(move || _parse_sitemap_xml(&xml_content))
+    .await
+    .map_err(|e| {
+      napi::Error::new(
+        napi::Status::GenericFailure,
+        format!("parse_sitemap_xml join error: {e}"),
+      )
+    })?;
+
+  res.map_err(|e| {
+    Error::new(
+      Status::GenericFailure,
+      format!("Parse sitemap XML error: {e}"),
+    )
+  })
+}
+
+/// Parses a W3C datetime as used by `<lastmod>`, from `YYYY` up to full timestamps.
+/// Values without a timezone are taken as UTC.
+fn parse_sitemap_datetime(value: &str) -> Option<DateTime<Utc>> {
+  let value = value.trim();
+  if value.is_empty() {
+    return None;
+  }
+
+  let with_offset = match value.strip_suffix(['Z', 'z']) {
+    Some(rest) => format!("{rest}+00:00"),
+    None => value.to_string(),
+  };
+
+  if let Ok(dt) = DateTime::parse_from_rfc3339(&with_offset) {
+    return Some(dt.with_timezone(&Utc));
+  }
+  if let Ok(dt) = DateTime::parse_from_str(&with_offset, "%Y-%m-%dT%H:%M%:z") {
+    return Some(dt.with_timezone(&Utc));
+  }
+
+  for format in [
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S",
+    "%Y-%m-%dT%H:%M",
+  ] {
+    if let Ok(dt) = NaiveDateTime::parse_from_str(value, format) {
+      return Some(dt.and_utc());
+    }
+  }
+
+  let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+    .or_else(|_| NaiveDate::parse_from_str(&format!("{value}-01"), "%Y-%m-%d"))
+    .or_else(|_| NaiveDate::parse_from_str(&format!("{value}-01-01"), "%Y-%m-%d"))
+    .ok()?;
+  date.and_hms_opt(0, 0, 0).map(|dt| dt.and_utc())
+}
+
+/// Decides whether a sitemap entry changed since the last crawl.
+#[derive(Default)]
+struct SitemapChangeFilter {
+  since: Option<DateTime<Utc>>,
+  known_lastmod: HashMap<String, DateTime<Utc>>,
+}
+
+impl SitemapChangeFilter {
+  fn new(options: SitemapProcessOptions) -> std::result::Result<Self, String> {
+    let since = match options.since {
+      Some(since) => Some(
+        parse_sitemap_datetime(&since)
+          .ok_or_else(|| format!("Invalid since timestamp: {since}"))?,
+      ),
+      None => None,
+    };
+
+    let known_lastmod = options
+      .known_lastmod
+      .unwrap_or_default()
+      .into_iter()
+      .filter_map(|(url, lastmod)| {
+        Some((url.trim().to_string(), parse_sitemap_datetime(&lastmod)?))
+      })
+      .collect();
+
+    Ok(Self {
+      since,
+      known_lastmod,
+    })
+  }
+
+  /// Entries without a (parseable) `<lastmod>` are always considered changed.
+  fn is_changed(&self, url: &str, lastmod: Option<&str>) -> bool {
+    let Some(lastmod) = lastmod.and_then(parse_sitemap_datetime) else {
+      return true;
+    };
+
+    match self.known_lastmod.get(url).or(self.since.as_ref()) {
+      Some(threshold) => lastmod > *threshold,
+      None => true,
+    }
+  }
+}
+
+/// Sorts sitemap entries into "recurse" and "process" instructions.
+struct SitemapInstructionBuilder {
+  filter: SitemapChangeFilter,
+  sitemaps: Vec<SitemapUrl>,
+  pages: Vec<SitemapUrl>,
+  skipped_count: u32,
+}
+
+impl SitemapInstructionBuilder {
+  fn new(filter: SitemapChangeFilter) -> Self {
+    Self {
+      filter,
+      sitemaps: Vec::new(),
+      pages: Vec::new(),
+      skipped_count: 0,
+    }
+  }
+
+  fn accept(&mut self, entry: &SitemapUrl) -> bool {
+    if self
+      .filter
+      .is_changed(&entry.loc[0], entry.lastmod.as_deref())
+    {
+      true
+    } else {
+      self.skipped_count += 1;
+      false
+    }
+  }
+
+  /// Adds a child sitemap listed in a `<sitemapindex>`.
+  fn push_sitemap(&mut self, mut entry: SitemapUrl) {
+    if entry.loc.is_empty() {
+      return;
+    }
+    entry.loc = vec![entry.loc[0].trim().to_string()];
+
+    if self.accept(&entry) {
+      self.sitemaps.push(entry);
+    }
+  }
+
+  /// Adds a `<url>` entry, which may itself point to another sitemap.
+  fn push_url(&mut self, mut entry: SitemapUrl) {
+    if entry.loc.is_empty() {
+      return;
+    }
+    let url = entry.loc[0].trim().to_string();
+    let url_lower = url.to_lowercase();
+    entry.loc = vec![url];
+
+    if url_lower.ends_with(".xml") || url_lower.ends_with(".xml.gz") {
+      if self.accept(&entry) {
+        self.sitemaps.push(entry);
+      }
+    } else if let Ok(parsed_url) = Url::parse(&entry.loc[0]) {
+      let path_lower = parsed_url.path().to_lowercase();
+      if !is_file(&path_lower) && self.accept(&entry) {
+        self.pages.push(entry);
+      }
+    }
+  }
+
+  fn finish(self) -> SitemapProcessingResult {
+    let mut instructions = Vec::new();
+    let mut total_count: u32 = 0;
+
+    for (action, entries) in [("recurse", self.sitemaps), ("process", self.pages)] {
+      if !entries.is_empty() {
+        let count = entries.len() as u32;
+        instructions.push(SitemapInstruction {
+          action: action.to_string(),
+          urls: entries.iter().map(|entry| entry.loc[0].clone()).collect(),
+          count,
+          entries,
+        });
+        total_count += count;
+      }
+    }
+
+    SitemapProcessingResult {
+      instructions,
+      total_count,
+      skipped_count: self.skipped_count,
+      truncated: false,
+      truncation_reason: None,
+      format: SITEMAP_FORMAT_XML.to_string(),
+    }
+  }
+}
+
+/// Plain-text sitemaps hold one URL per line; anything that is not an http(s) URL is ignored.
+fn parse_text_sitemap_line(line: &str) -> Option<SitemapUrl> {
+  let line = line.trim().trim_start_matches('\u{feff}');
+  let url = Url::parse(line).ok()?;
+  if url.scheme() != "http" && url.scheme() != "https" {
+    return None;
+  }
+
+  Some(SitemapUrl {
+    loc: vec![line.to_string()],
+    ..Default::default()
+  })
+}
+
+#[inline]
+fn is_markup(content: &str) -> bool {
+  content
+    .trim_start_matches('\u{feff}')
+    .trim_start()
+    .starts_with('<')
+}
+
+fn _process_sitemap(
+  xml_content: &str,
+  options: Option<SitemapProcessOptions>,
+) -> std::result::Result<SitemapProcessingResult, String> {
+  let filter = SitemapChangeFilter::new(options.unwrap_or_default())?;
+  let mut builder = SitemapInstructionBuilder::new(filter);
+
+  if !is_markup(xml_content) {
+    for entry in xml_content.lines().filter_map(parse_text_sitemap_line) {
+      builder.push_url(entry);
+    }
+
+    let mut result = builder.finish();
+    result.format = SITEMAP_FORMAT_TEXT.to_string();
+    return Ok(result);
+  }
+
+  let parsed = _parse_sitemap_xml(xml_content)?;
+
+  if let Some(sitemapindex) = parsed.sitemapindex {
+    for sitemap in sitemapindex.sitemap {
+      builder.push_sitemap(SitemapUrl {
+        loc: sitemap.loc,
+        lastmod: sitemap.lastmod,
+        ..Default::default()
+      });
+    }
+  } else if let Some(urlset) = parsed.urlset {
+    for url_entry in urlset.url {
+      builder.push_url(url_entry);
+    }
+  }
+
+  let mut result = builder.finish();
+  result.format = parsed.format;
+  Ok(result)
+}
+
+/// Process sitemap XML and extract crawling instructions.
+///
+/// Besides `urlset`/`sitemapindex` documents this accepts plain-text URL lists and RSS 2.0 /
+/// Atom feeds, whose item dates are used as `<lastmod>`.
+///
+/// With `options.since` / `options.knownLastmod`, entries whose `<lastmod>` has not moved
+/// forward are dropped from the instructions, so recrawls only fetch what changed.
+#[napi]
+pub async fn process_sitemap(
+  xml_content: String,
+  options: Option<SitemapProcessOptions>,
+) -> Result<SitemapProcessingResult> {
+  let res = task::spawn_blocking(move || _process_sitemap(&xml_content, options))
+    .await
+    .map_err(|e| {
+      napi::Error::new(
+        napi::Status::GenericFailure,
+        format!("process_sitemap join error: {e}"),
+      )
+    })?;
+
+  res.map_err(|e| Error::new(Status::GenericFailure, format!("Parse sitemap error: {e}")))
+}
+
+/// Reader that stops after `remaining` bytes and remembers whether there was more input.
+struct LimitedReader<R> {
+  inner: R,
+  remaining: u64,
+  exceeded: bool,
+}
+
+impl<R: Read> LimitedReader<R> {
+  fn new(inner: R, limit: u64) -> Self {
+    Self {
+      inner,
+      remaining: limit,
+      exceeded: false,
+    }
+  }
+}
+
+impl<R: Read> Read for LimitedReader<R> {
+  fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+    if buf.is_empty() {
+      return Ok(0);
+    }
+
+    if self.remaining == 0 {
+      if !self.exceeded {
+        let mut probe = [0u8; 1];
+        self.exceeded = self.inner.read(&mut probe)? > 0;
+      }
+      return Ok(0);
+    }
+
+    let max = buf
+      .len()
+      .min(usize::try_from(self.remaining).unwrap_or(usize::MAX));
+    let read = self.inner.read(&mut buf[..max])?;
+    self.remaining -= read as u64;
+    Ok(read)
+  }
+}
+
+/// Element of a sitemap document that collects fields while it is being streamed.
+enum SitemapStreamElement {
+  Url(SitemapUrl),
+  Sitemap(SitemapUrl),
+  FeedEntry(SitemapUrl),
+  Image(SitemapImage),
+  News(SitemapNews),
+  Video(SitemapVideo),
+}
+
+type SitemapInput<'a> = BufReader<LimitedReader<Box<dyn Read + 'a>>>;
+
+/// Skips a UTF-8 BOM and leading whitespace, returning the first meaningful byte.
+fn peek_first_byte(input: &mut impl BufRead) -> io::Result<Option<u8>> {
+  if input.fill_buf()?.starts_with(&[0xef, 0xbb, 0xbf]) {
+    input.consume(3);
+  }
+
+  loop {
+    let buf = input.fill_buf()?;
+    if buf.is_empty() {
+      return Ok(None);
+    }
+
+    let whitespace = buf.iter().take_while(|b| b.is_ascii_whitespace()).count();
+    if whitespace < buf.len() {
+      let first = buf[whitespace];
+      input.consume(whitespace);
+      return Ok(Some(first));
+    }
+
+    let len = buf.len();
+    input.consume(len);
+  }
+}
+
+fn stream_text_sitemap(
+  input: &mut SitemapInput,
+  builder: &mut SitemapInstructionBuilder,
+  max_urls: u32,
+) -> std::result::Result<Option<&'static str>, String> {
+  let mut line = Vec::new();
+  let mut entry_count: u32 = 0;
+
+  loop {
+    line.clear();
+    match input.read_until(b'\n', &mut line) {
+      Ok(0) => return Ok(None),
+      Ok(_) => {}
+      Err(_) if input.get_ref().exceeded => return Ok(None),
+      Err(e) => return Err(format!("Read error: {e}")),
+    }
+
+    // A line cut off by the byte limit would yield a truncated URL.
+    if !line.ends_with(b"\n") && input.get_ref().exceeded {
+      return Ok(None);
+    }
+
+    if let Some(entry) = parse_text_sitemap_line(&String::from_utf8_lossy(&line)) {
+      if entry_count >= max_urls {
+        return Ok(Some(SITEMAP_TRUNCATED_MAX_URLS));
+      }
+      entry_count += 1;
+      builder.push_url(entry);
+    }
+  }
+}
+
+fn stream_xml_sitemap(
+  input: &mut SitemapInput,
+  builder: &mut SitemapInstructionBuilder,
+  max_urls: u32,
+) -> std::result::Result<(&'static str, Option<&'static str>), String> {
+  let mut reader = Reader::from_reader(input);
+  let mut buf = Vec::new();
+  let mut text = String::new();
+  let mut format = None;
+  let mut stack: Vec<SitemapStreamElement> = Vec::new();
+  let mut entry_count: u32 = 0;
+
+  loop {
+    buf.clear();
+    let event = match reader.read_event_into(&mut buf) {
+      Ok(event) => event,
+      Err(_) if reader.get_ref().get_ref().exceeded => break,
+      Err(e) => return Err(format!("XML parsing error: {e}")),
+    };
+
+    match event {
+      Event::Start(ref e) | Event::Empty(ref e) => {
+        let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+        text.clear();
+
+        let Some(root) = format else {
+          format = Some(match name.as_str() {
+            "urlset" | "sitemapindex" => SITEMAP_FORMAT_XML,
+            "rss" => SITEMAP_FORMAT_RSS,
+            "feed" => SITEMAP_FORMAT_ATOM,
+            _ => return Err(INVALID_SITEMAP_ROOT.to_string()),
+          });
+          continue;
+        };
+
+        let element = match (stack.last_mut(), name.as_str()) {
+          (None, "url") if root == SITEMAP_FORMAT_XML => {
+            Some(SitemapStreamElement::Url(SitemapUrl::default()))
+          }
+          (None, "sitemap") if root == SITEMAP_FORMAT_XML => {
+            Some(SitemapStreamElement::Sitemap(SitemapUrl::default()))
+          }
+          (None, "item") if root == SITEMAP_FORMAT_RSS => {
+            Some(SitemapStreamElement::FeedEntry(SitemapUrl::default()))
+          }
+          (None, "entry") if root == SITEMAP_FORMAT_ATOM => {
+            Some(SitemapStreamElement::FeedEntry(SitemapUrl::default()))
+          }
+          (Some(SitemapStreamElement::Url(_)), "image") => {
+            Some(SitemapStreamElement::Image(SitemapImage::default()))
+          }
+          (Some(SitemapStreamElement::Url(_)), "news") => {
+            Some(SitemapStreamElement::News(SitemapNews::default()))
+          }
+          (Some(SitemapStreamElement::Url(_)), "video") => {
+            Some(SitemapStreamElement::Video(SitemapVideo::default()))
+          }
+          (Some(SitemapStreamElement::Url(entry)), "link")
+          | (Some(SitemapStreamElement::FeedEntry(entry)), "link") => {
+            let mut rel = None;
+            let mut hreflang = None;
+            let mut href = None;
+            for attr in e.attributes().flatten() {
+              let value = attr
+                .unescape_value()
+                .map(|v| v.into_owned())
+                .unwrap_or_default();
+              match attr.key.local_name().as_ref() {
+                b"rel" => rel = Some(value),
+                b"hreflang" => hreflang = Some(value),
+                b"href" => href = Some(value),
+                _ => {}
+              }
+            }
+
+            if root == SITEMAP_FORMAT_XML {
+              entry.alternates.extend(sitemap_alternate(
+                rel.as_deref(),
+                hreflang.as_deref(),
+                href.as_deref(),
+              ));
+            } else if let Some(href) = href.filter(|_| is_atom_alternate_link(rel.as_deref())) {
+              apply_feed_entry_field(entry, "link", href.trim());
+            }
+            None
+          }
+          _ => None,
+        };
+
+        if let Some(element) = element {
+          if stack.is_empty() && entry_count >= max_urls {
+            return Ok((root, Some(SITEMAP_TRUNCATED_MAX_URLS)));
+          }
+          // Self-closing entries carry no fields, so only open elements are tracked.
+          if matches!(event, Event::Start(_)) {
+            stack.push(element);
+          }
+        }
+      }
+      Event::Text(e) => {
+        text.push_str(
+          &e.unescape()
+            .map_err(|e| format!("XML parsing error: {e}"))?,
+        );
+      }
+      Event::CData(e) => text.push_str(&String::from_utf8_lossy(&e.into_inner())),
+      Event::End(ref e) => {
+        let name = String::from_utf8_lossy(e.local_name().as_ref()).into_owned();
+        let value = text.trim();
+
+        let closes_element = matches!(
+          (stack.last(), name.as_str()),
+          (Some(SitemapStreamElement::Url(_)), "url")
+            | (Some(SitemapStreamElement::Sitemap(_)), "sitemap")
+            | (Some(SitemapStreamElement::FeedEntry(_)), "item" | "entry")
+            | (Some(SitemapStreamElement::Image(_)), "image")
+            | (Some(SitemapStreamElement::News(_)), "news")
+            | (Some(SitemapStreamElement::Video(_)), "video")
+        );
+
+        if closes_element {
+          match stack.pop() {
+            Some(SitemapStreamElement::Url(entry))
+            | Some(SitemapStreamElement::FeedEntry(entry)) => {
+              entry_count += 1;
+              builder.push_url(entry);
+            }
+            Some(SitemapStreamElement::Sitemap(entry)) => {
+              entry_count += 1;
+              builder.push_sitemap(entry);
+            }
+            Some(SitemapStreamElement::Image(image)) => {
+              if let Some(SitemapStreamElement::Url(entry)) = stack.last_mut() {
+                if !image.loc.is_empty() {
+                  entry.images.push(image);
+                }
+              }
+            }
+            Some(SitemapStreamElement::News(news)) => {
+              if let Some(SitemapStreamElement::Url(entry)) = stack.last_mut() {
+                entry.news = Some(news);
+              }
+            }
+            Some(SitemapStreamElement::Video(video)) => {
+              if let Some(SitemapStreamElement::Url(entry)) = stack.last_mut() {
+                entry.videos.push(video);
+              }
+            }
+            None => {}
+          }
+        } else if !value.is_empty() {
+          match stack.last_mut() {
+            Some(SitemapStreamElement::Url(entry)) | Some(SitemapStreamElement::Sitemap(entry)) => {
+              apply_sitemap_url_field(entry, &name, value)
+            }
+            Some(SitemapStreamElement::FeedEntry(entry)) => {
+              apply_feed_entry_field(entry, &name, value)
+            }
+            Some(SitemapStreamElement::Image(image)) => {
+              apply_sitemap_image_field(image, &name, value)
+            }
+            Some(SitemapStreamElement::News(news)) => apply_sitemap_news_field(news, &name, value),
+            Some(SitemapStreamElement::Video(video)) => {
+              apply_sitemap_video_field(video, &name, value)
+            }
+            None => {}
+          }
+        }
+
+        text.clear();
+      }
+      Event::Eof => break,
+      _ => {}
+    }
+  }
+
+  match format {
+    Some(format) => Ok((format, None)),
+    None if reader.get_ref().get_ref().exceeded => Ok((SITEMAP_FORMAT_XML, None)),
+    None => Err(INVALID_SITEMAP_ROOT.to_string()),
+  }
+}
+
+fn _process_sitemap_bytes(
+  data: &[u8],
+  options: Option<SitemapProcessOptions>,
+) -> std::result::Result<SitemapProcessingResult, String> {
+  let options = options.unwrap_or_default();
+  let max_urls = options.max_urls.unwrap_or(SITEMAP_MAX_URLS);
+  let max_bytes = options.max_bytes.unwrap_or(SITEMAP_MAX_BYTES);
+  let mut builder = SitemapInstructionBuilder::new(SitemapChangeFilter::new(options)?);
+
+  let source: Box<dyn Read + '_> = if data.starts_with(&[0x1f, 0x8b]) {
+    Box::new(MultiGzDecoder::new(data))
+  } else {
+    Box::new(data)
+  };
+  let mut input = BufReader::new(LimitedReader::new(source, u64::from(max_bytes)));
+
+  let first_byte = peek_first_byte(&mut input).map_err(|e| format!("Read error: {e}"))?;
+  let (format, mut truncation_reason) = if first_byte == Some(b'<') {
+    stream_xml_sitemap(&mut input, &mut builder, max_urls)?
+  } else {
+    (
+      SITEMAP_FORMAT_TEXT,
+      stream_text_sitemap(&mut input, &mut builder, max_urls)?,
+    )
+  };
+
+  if truncation_reason.is_none() && input.get_ref().exceeded {
+    truncation_reason = Some(SITEMAP_TRUNCATED_MAX_BYTES);
+  }
+
+  let mut result = builder.finish();
+  result.format = format.to_string();
+  result.truncated = truncation_reason.is_some();
+  result.truncation_reason = truncation_reason.map(|reason| reason.to_string());
+  Ok(result)
+}
+
+/// Process raw sitemap bytes (plain or gzip-compressed) and extract crawling instructions.
+/// Accepts the same formats as `process_sitemap`.
+///
+/// Only decompression and parsing are streamed: the input buffer stays resident, but the
+/// decompressed document is never held in memory as a whole. Reading stops at `options.maxUrls`
+/// entries or `options.maxBytes` uncompressed bytes (the sitemaps.org limits by default), and
+/// the result is flagged as truncated.
+#[napi]
+pub async fn process_sitemap_bytes(
+  data: Buffer,
+  options: Option<SitemapProcessOptions>,
+) -> Result<SitemapProcessingResult> {
+  let res = task::spawn_blocking(move || _process_sitemap_bytes(&data, options))
+    .await
+    .map_err(|e| {
+      napi::Error::new(
+        napi::Status::GenericFailure,
+        format!("process_sitemap_bytes join error: {e}"),
+      )
+    })?;
+
+  res.map_err(|e| Error::new(Status::GenericFailure, format!("Parse sitemap error: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::robots::{_parse_robots_txt, ParseRobotsTxtCall};
+  use chrono::TimeZone;
+
+  #[test]
+  fn test_parse_sitemap_xml_urlset() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>https://example.com/page1</loc>
+  </url>
+  <url>
+    <loc>https://example.com/page2</loc>
+  </url>
+</urlset>"#;
+
+    let result = _parse_sitemap_xml(xml_content).unwrap();
+    assert!(result.urlset.is_some());
+    let urlset = result.urlset.unwrap();
+    assert_eq!(urlset.url.len(), 2);
+    assert_eq!(urlset.url[0].loc[0], "https://example.com/page1");
+    assert_eq!(urlset.url[1].loc[0], "https://example.com/page2");
+  }
+
+  #[test]
+  fn test_parse_sitemap_xml_sitemapindex() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sitemap>
+    <loc>https://example.com/sitemap1.xml</loc>
+  </sitemap>
+  <sitemap>
+    <loc>https://example.com/sitemap2.xml</loc>
+  </sitemap>
+</sitemapindex>"#;
+
+    let result = _parse_sitemap_xml(xml_content).unwrap();
     assert!(result.sitemapindex.is_some());
     let sitemapindex = result.sitemapindex.unwrap();
     assert_eq!(sitemapindex.sitemap.len(), 2);
     assert_eq!(
-      sitemapindex.sitemap[0].loc[0],
-      "https://example.com/sitemap1.xml"
+      sitemapindex.sitemap[0].loc[0],
+      "https://example.com/sitemap1.xml"
+    );
+    assert_eq!(
+      sitemapindex.sitemap[1].loc[0],
+      "https://example.com/sitemap2.xml"
+    );
+  }
+
+  #[test]
+  fn test_parse_sitemap_xml_protocol_fields_and_extensions() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+        xmlns:image="http://www.google.com/schemas/sitemap-image/1.1"
+        xmlns:news="http://www.google.com/schemas/sitemap-news/0.9"
+        xmlns:video="http://www.google.com/schemas/sitemap-video/1.1"
+        xmlns:xhtml="http://www.w3.org/1999/xhtml">
+  <url>
+    <loc>https://example.com/article</loc>
+    <lastmod>2024-05-01T10:00:00+00:00</lastmod>
+    <changefreq>Daily</changefreq>
+    <priority>0.8</priority>
+    <xhtml:link rel="alternate" hreflang="de" href="https://example.com/de/article"/>
+    <image:image>
+      <image:loc>https://example.com/a.jpg</image:loc>
+      <image:title>A</image:title>
+    </image:image>
+    <news:news>
+      <news:publication>
+        <news:name>Example Times</news:name>
+        <news:language>en</news:language>
+      </news:publication>
+      <news:publication_date>2024-05-01</news:publication_date>
+      <news:title>Headline</news:title>
+    </news:news>
+    <video:video>
+      <video:thumbnail_loc>https://example.com/t.jpg</video:thumbnail_loc>
+      <video:title>Clip</video:title>
+      <video:duration>120</video:duration>
+      <video:family_friendly>yes</video:family_friendly>
+    </video:video>
+  </url>
+  <url>
+    <loc>https://example.com/plain</loc>
+    <changefreq>sometimes</changefreq>
+    <priority>1.5</priority>
+  </url>
+</urlset>"#;
+
+    let urlset = _parse_sitemap_xml(xml_content).unwrap().urlset.unwrap();
+    assert_eq!(urlset.url.len(), 2);
+
+    let article = &urlset.url[0];
+    assert_eq!(
+      article.lastmod.as_deref(),
+      Some("2024-05-01T10:00:00+00:00")
+    );
+    assert_eq!(article.changefreq.as_deref(), Some("daily"));
+    assert_eq!(article.priority, Some(0.8));
+    assert_eq!(article.alternates.len(), 1);
+    assert_eq!(article.alternates[0].hreflang, "de");
+    assert_eq!(article.alternates[0].href, "https://example.com/de/article");
+    assert_eq!(article.images.len(), 1);
+    assert_eq!(article.images[0].loc, "https://example.com/a.jpg");
+    assert_eq!(article.images[0].title.as_deref(), Some("A"));
+    let news = article.news.as_ref().unwrap();
+    assert_eq!(news.publication_name.as_deref(), Some("Example Times"));
+    assert_eq!(news.publication_language.as_deref(), Some("en"));
+    assert_eq!(news.title.as_deref(), Some("Headline"));
+    assert_eq!(article.videos.len(), 1);
+    assert_eq!(article.videos[0].duration, Some(120));
+    assert_eq!(article.videos[0].family_friendly, Some(true));
+
+    let plain = &urlset.url[1];
+    assert!(plain.lastmod.is_none());
+    assert!(plain.changefreq.is_none());
+    assert!(plain.priority.is_none());
+    assert!(plain.images.is_empty());
+  }
+
+  #[test]
+  fn test_parse_sitemap_xml_invalid_root() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<invalid xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>https://example.com/page1</loc>
+  </url>
+</invalid>"#;
+
+    let result = _parse_sitemap_xml(xml_content);
+    assert!(result.is_err());
+    assert!(result.unwrap_err().contains("Invalid sitemap format"));
+  }
+
+  #[test]
+  fn test_parse_sitemap_xml_malformed() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>https://example.com/page1</loc>
+  </url>
+</urlset"#; // Missing closing >
+
+    let result = _parse_sitemap_xml(xml_content);
+    assert!(result.is_err());
+  }
+
+  #[test]
+  fn test_process_sitemap_urlset() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>https://example.com/page1</loc>
+  </url>
+  <url>
+    <loc>https://example.com/sitemap2.xml</loc>
+  </url>
+  <url>
+    <loc>https://example.com/image.png</loc>
+  </url>
+</urlset>"#;
+
+    let result = _process_sitemap(xml_content, None).unwrap();
+    assert_eq!(result.instructions.len(), 2);
+
+    let recurse_instruction = result
+      .instructions
+      .iter()
+      .find(|i| i.action == "recurse")
+      .unwrap();
+    assert_eq!(recurse_instruction.urls.len(), 1);
+    assert_eq!(
+      recurse_instruction.urls[0],
+      "https://example.com/sitemap2.xml"
+    );
+
+    let process_instruction = result
+      .instructions
+      .iter()
+      .find(|i| i.action == "process")
+      .unwrap();
+    assert_eq!(process_instruction.urls.len(), 1);
+    assert_eq!(process_instruction.urls[0], "https://example.com/page1");
+  }
+
+  #[test]
+  fn test_process_sitemap_exposes_entry_metadata() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sitemap>
+    <loc> https://example.com/sitemap1.xml </loc>
+    <lastmod>2024-01-01</lastmod>
+  </sitemap>
+</sitemapindex>"#;
+
+    let result = _process_sitemap(xml_content, None).unwrap();
+    let entries = &result.instructions[0].entries;
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].loc[0], "https://example.com/sitemap1.xml");
+    assert_eq!(entries[0].lastmod.as_deref(), Some("2024-01-01"));
+
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url>
+    <loc>https://example.com/page1</loc>
+    <lastmod>2024-02-01</lastmod>
+    <priority>0.3</priority>
+  </url>
+</urlset>"#;
+
+    let result = _process_sitemap(xml_content, None).unwrap();
+    let process_instruction = &result.instructions[0];
+    assert_eq!(process_instruction.action, "process");
+    assert_eq!(process_instruction.entries.len(), 1);
+    assert_eq!(
+      process_instruction.entries[0].lastmod.as_deref(),
+      Some("2024-02-01")
+    );
+    assert_eq!(process_instruction.entries[0].priority, Some(0.3));
+  }
+
+  #[test]
+  fn test_parse_sitemap_datetime_formats() {
+    let expected = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+    assert_eq!(parse_sitemap_datetime("2024-05-01"), Some(expected));
+    assert_eq!(
+      parse_sitemap_datetime("2024-05-01T00:00:00Z"),
+      Some(expected)
+    );
+    assert_eq!(
+      parse_sitemap_datetime("2024-05-01T02:00+02:00"),
+      Some(expected)
+    );
+    assert_eq!(
+      parse_sitemap_datetime("2024-05-01T00:00:00.000"),
+      Some(expected)
+    );
+    assert_eq!(
+      parse_sitemap_datetime("2024-05"),
+      Some(Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap())
+    );
+    assert_eq!(
+      parse_sitemap_datetime("2024"),
+      Some(Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap())
+    );
+    assert_eq!(parse_sitemap_datetime("yesterday"), None);
+  }
+
+  #[test]
+  fn test_process_sitemap_since_filters_urls() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <url><loc>https://example.com/old</loc><lastmod>2024-01-01</lastmod></url>
+  <url><loc>https://example.com/new</loc><lastmod>2024-03-01T12:00:00Z</lastmod></url>
+  <url><loc>https://example.com/undated</loc></url>
+  <url><loc>https://example.com/known</loc><lastmod>2024-03-01</lastmod></url>
+</urlset>"#;
+
+    let options = SitemapProcessOptions {
+      since: Some("2024-02-01".to_string()),
+      known_lastmod: Some(HashMap::from([(
+        "https://example.com/known".to_string(),
+        "2024-03-01".to_string(),
+      )])),
+      ..Default::default()
+    };
+
+    let result = _process_sitemap(xml_content, Some(options)).unwrap();
+    assert_eq!(result.instructions.len(), 1);
+    assert_eq!(
+      result.instructions[0].urls,
+      vec!["https://example.com/new", "https://example.com/undated"]
+    );
+    assert_eq!(result.total_count, 2);
+    assert_eq!(result.skipped_count, 2);
+  }
+
+  #[test]
+  fn test_process_sitemap_since_prunes_child_sitemaps() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sitemap><loc>https://example.com/sitemap-2023.xml</loc><lastmod>2023-12-31</lastmod></sitemap>
+  <sitemap><loc>https://example.com/sitemap-2024.xml</loc><lastmod>2024-06-01</lastmod></sitemap>
+</sitemapindex>"#;
+
+    let options = SitemapProcessOptions {
+      since: Some("2024-01-01T00:00:00Z".to_string()),
+      ..Default::default()
+    };
+
+    let result = _process_sitemap(xml_content, Some(options)).unwrap();
+    assert_eq!(result.instructions.len(), 1);
+    assert_eq!(result.instructions[0].action, "recurse");
+    assert_eq!(
+      result.instructions[0].urls,
+      vec!["https://example.com/sitemap-2024.xml"]
+    );
+    assert_eq!(result.skipped_count, 1);
+
+    let options = SitemapProcessOptions {
+      since: Some("not a date".to_string()),
+      ..Default::default()
+    };
+    assert!(_process_sitemap(xml_content, Some(options)).is_err());
+  }
+
+  #[test]
+  fn test_process_sitemap_sitemapindex() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sitemap>
+    <loc>https://example.com/sitemap1.xml</loc>
+  </sitemap>
+  <sitemap>
+    <loc>https://example.com/sitemap2.xml</loc>
+  </sitemap>
+</sitemapindex>"#;
+
+    let result = _process_sitemap(xml_content, None).unwrap();
+    assert_eq!(result.instructions.len(), 1);
+    assert_eq!(result.instructions[0].action, "recurse");
+    assert_eq!(result.instructions[0].urls.len(), 2);
+    assert_eq!(
+      result.instructions[0].urls[0],
+      "https://example.com/sitemap1.xml"
+    );
+    assert_eq!(
+      result.instructions[0].urls[1],
+      "https://example.com/sitemap2.xml"
+    );
+  }
+
+  const RSS_FEED: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom">
+  <channel>
+    <title>Example</title>
+    <link>https://example.com/</link>
+    <atom:link href="https://example.com/feed.xml" rel="self" type="application/rss+xml"/>
+    <item>
+      <title>First</title>
+      <link>https://example.com/posts/first</link>
+      <pubDate>Wed, 01 May 2024 10:00:00 GMT</pubDate>
+    </item>
+    <item>
+      <title>Second</title>
+      <guid isPermaLink="true">https://example.com/posts/second</guid>
+      <pubDate>Mon, 01 Jan 2024 10:00:00 +0000</pubDate>
+    </item>
+  </channel>
+</rss>"#;
+
+  const ATOM_FEED: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <title>Example</title>
+  <link href="https://example.com/" rel="alternate"/>
+  <entry>
+    <id>tag:example.com,2024:1</id>
+    <link rel="alternate" href="https://example.com/posts/first"/>
+    <link rel="edit" href="https://example.com/edit/1"/>
+    <published>2024-04-01T00:00:00Z</published>
+    <updated>2024-05-01T10:00:00Z</updated>
+  </entry>
+  <entry>
+    <id>https://example.com/posts/second</id>
+    <updated>2024-01-01T10:00:00Z</updated>
+  </entry>
+</feed>"#;
+
+  const TEXT_SITEMAP: &str = "https://example.com/a\n\n  https://example.com/b  \nnot a url\nftp://example.com/file\nhttps://example.com/sitemap-2.xml\n";
+
+  #[test]
+  fn test_process_sitemap_non_xml_formats() {
+    let result = _process_sitemap(TEXT_SITEMAP, None).unwrap();
+    assert_eq!(result.format, "text");
+    let process = result
+      .instructions
+      .iter()
+      .find(|i| i.action == "process")
+      .unwrap();
+    assert_eq!(
+      process.urls,
+      vec!["https://example.com/a", "https://example.com/b"]
+    );
+    let recurse = result
+      .instructions
+      .iter()
+      .find(|i| i.action == "recurse")
+      .unwrap();
+    assert_eq!(recurse.urls, vec!["https://example.com/sitemap-2.xml"]);
+
+    let result = _process_sitemap(RSS_FEED, None).unwrap();
+    assert_eq!(result.format, "rss");
+    assert_eq!(
+      result.instructions[0].urls,
+      vec![
+        "https://example.com/posts/first",
+        "https://example.com/posts/second"
+      ]
+    );
+    assert_eq!(
+      result.instructions[0].entries[0].lastmod.as_deref(),
+      Some("2024-05-01T10:00:00+00:00")
+    );
+
+    let result = _process_sitemap(ATOM_FEED, None).unwrap();
+    assert_eq!(result.format, "atom");
+    assert_eq!(
+      result.instructions[0].urls,
+      vec![
+        "https://example.com/posts/first",
+        "https://example.com/posts/second"
+      ]
+    );
+    assert_eq!(
+      result.instructions[0].entries[0].lastmod.as_deref(),
+      Some("2024-05-01T10:00:00Z")
+    );
+  }
+
+  #[test]
+  fn test_process_sitemap_feed_since_uses_item_dates() {
+    for feed in [RSS_FEED, ATOM_FEED] {
+      let options = SitemapProcessOptions {
+        since: Some("2024-03-01".to_string()),
+        ..Default::default()
+      };
+      let result = _process_sitemap(feed, Some(options)).unwrap();
+      assert_eq!(
+        result.instructions[0].urls,
+        vec!["https://example.com/posts/first"]
+      );
+      assert_eq!(result.skipped_count, 1);
+    }
+  }
+
+  #[test]
+  fn test_process_sitemap_bytes_non_xml_formats() {
+    for content in [TEXT_SITEMAP, RSS_FEED, ATOM_FEED] {
+      let from_string = _process_sitemap(content, None).unwrap();
+      let from_bytes = _process_sitemap_bytes(&gzip(content.as_bytes()), None).unwrap();
+      assert_eq!(from_bytes.format, from_string.format);
+      assert_eq!(from_bytes.total_count, from_string.total_count);
+      for (a, b) in from_bytes
+        .instructions
+        .iter()
+        .zip(from_string.instructions.iter())
+      {
+        assert_eq!(a.action, b.action);
+        assert_eq!(a.urls, b.urls);
+        for (x, y) in a.entries.iter().zip(b.entries.iter()) {
+          assert_eq!(x.lastmod, y.lastmod);
+        }
+      }
+    }
+
+    let options = SitemapProcessOptions {
+      max_urls: Some(1),
+      ..Default::default()
+    };
+    let result = _process_sitemap_bytes(TEXT_SITEMAP.as_bytes(), Some(options)).unwrap();
+    assert_eq!(result.truncation_reason.as_deref(), Some("MAX_URLS"));
+    assert_eq!(result.total_count, 1);
+  }
+
+  fn gzip(data: &[u8]) -> Vec<u8> {
+    use flate2::{write::GzEncoder, Compression};
+    use std::io::Write;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+  }
+
+  #[test]
+  fn test_process_sitemap_bytes_matches_string_parser() {
+    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9"
+        xmlns:image="http://www.google.com/schemas/sitemap-image/1.1"
+        xmlns:xhtml="http://www.w3.org/1999/xhtml">
+  <url>
+    <loc>https://example.com/page1?a=1&amp;b=2</loc>
+    <lastmod>2024-05-01</lastmod>
+    <priority>0.7</priority>
+    <xhtml:link rel="alternate" hreflang="fr" href="https://example.com/fr/page1"/>
+    <image:image><image:loc><![CDATA[https://example.com/a.jpg]]></image:loc></image:image>
+  </url>
+  <url>
+    <loc>https://example.com/sitemap2.xml</loc>
+  </url>
+  <url>
+    <loc>https://example.com/image.png</loc>
+  </url>
+</urlset>"#;
+
+    let from_string = _process_sitemap(xml_content, None).unwrap();
+    for data in [
+      xml_content.as_bytes().to_vec(),
+      gzip(xml_content.as_bytes()),
+    ] {
+      let from_bytes = _process_sitemap_bytes(&data, None).unwrap();
+      assert!(!from_bytes.truncated);
+      assert_eq!(from_bytes.total_count, from_string.total_count);
+      for (a, b) in from_bytes
+        .instructions
+        .iter()
+        .zip(from_string.instructions.iter())
+      {
+        assert_eq!(a.action, b.action);
+        assert_eq!(a.urls, b.urls);
+      }
+    }
+
+    let result = _process_sitemap_bytes(&gzip(xml_content.as_bytes()), None).unwrap();
+    let page = &result
+      .instructions
+      .iter()
+      .find(|i| i.action == "process")
+      .unwrap()
+      .entries[0];
+    assert_eq!(page.loc[0], "https://example.com/page1?a=1&b=2");
+    assert_eq!(page.lastmod.as_deref(), Some("2024-05-01"));
+    assert_eq!(page.priority, Some(0.7));
+    assert_eq!(page.alternates[0].hreflang, "fr");
+    assert_eq!(page.images[0].loc, "https://example.com/a.jpg");
+  }
+
+  #[test]
+  fn test_process_sitemap_bytes_sitemapindex_with_since() {
+    let xml_content = r#"<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
+  <sitemap><loc>https://example.com/a.xml</loc><lastmod>2023-01-01</lastmod></sitemap>
+  <sitemap><loc>https://example.com/b.xml</loc><lastmod>2025-01-01</lastmod></sitemap>
+</sitemapindex>"#;
+
+    let options = SitemapProcessOptions {
+      since: Some("2024-01-01".to_string()),
+      ..Default::default()
+    };
+    let result = _process_sitemap_bytes(xml_content.as_bytes(), Some(options)).unwrap();
+    assert_eq!(result.instructions.len(), 1);
+    assert_eq!(result.instructions[0].action, "recurse");
+    assert_eq!(
+      result.instructions[0].urls,
+      vec!["https://example.com/b.xml"]
+    );
+    assert_eq!(result.skipped_count, 1);
+  }
+
+  #[test]
+  fn test_process_sitemap_bytes_enforces_limits() {
+    let mut xml_content =
+      String::from(r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#);
+    for i in 0..10 {
+      xml_content.push_str(&format!("<url><loc>https://example.com/p{i}</loc></url>"));
+    }
+    xml_content.push_str("</urlset>");
+
+    let options = SitemapProcessOptions {
+      max_urls: Some(3),
+      ..Default::default()
+    };
+    let result = _process_sitemap_bytes(xml_content.as_bytes(), Some(options)).unwrap();
+    assert!(result.truncated);
+    assert_eq!(result.truncation_reason.as_deref(), Some("MAX_URLS"));
+    assert_eq!(result.total_count, 3);
+
+    let options = SitemapProcessOptions {
+      max_bytes: Some(150),
+      ..Default::default()
+    };
+    let result = _process_sitemap_bytes(&gzip(xml_content.as_bytes()), Some(options)).unwrap();
+    assert!(result.truncated);
+    assert_eq!(result.truncation_reason.as_deref(), Some("MAX_BYTES"));
+    assert!(result.total_count > 0 && result.total_count < 10);
+  }
+
+  #[test]
+  fn test_process_sitemap_bytes_rejects_invalid_documents() {
+    let result = _process_sitemap_bytes(b"<invalid><url><loc>x</loc></url></invalid>", None);
+    assert!(result.unwrap_err().contains("Invalid sitemap format"));
+
+    let result = _process_sitemap_bytes(
+      b"<urlset><url><loc>https://example.com/</loc></url></urlset",
+      None,
+    );
+    assert!(result.is_err());
+  }
+
+  #[test]
+  fn test_filter_links_normal_robots_txt() {
+    let data = FilterLinksCall {
+      links: vec![
+        "https://example.com/allowed".to_string(),
+        "https://example.com/disallowed".to_string(),
+      ],
+      limit: Some(10),
+      options: CrawlFilterOptions {
+        includes: vec![],
+        excludes: vec![],
+        ignore_robots_txt: false,
+        robots_txt: "User-agent: *\nDisallow: /disallowed".to_string(),
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        regex_on_full_url: false,
+        allow_backward_crawling: true,
+        allow_external_content_links: false,
+        allow_subdomains: false,
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    let result = _filter_links(data).unwrap();
+    assert_eq!(result.links.len(), 1);
+    assert_eq!(result.links[0], "https://example.com/allowed");
+    assert!(result
+      .denial_reasons
+      .contains_key("https://example.com/disallowed"));
+    assert_eq!(
+      result
+        .denial_reasons
+        .get("https://example.com/disallowed")
+        .unwrap(),
+      "ROBOTS_TXT"
+    );
+  }
+
+  #[test]
+  fn test_filter_links_malformed_robots_txt() {
+    let data = FilterLinksCall {
+      links: vec!["https://example.com/test".to_string()],
+      limit: Some(10),
+      options: CrawlFilterOptions {
+        includes: vec![],
+        excludes: vec![],
+        ignore_robots_txt: false,
+        robots_txt: "Invalid robots.txt content with \x00 null bytes and malformed syntax"
+          .to_string(),
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        regex_on_full_url: false,
+        allow_backward_crawling: true,
+        allow_external_content_links: false,
+        allow_subdomains: false,
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    let result = _filter_links(data);
+    assert!(result.is_ok());
+    let result = result.unwrap();
+    assert_eq!(result.links.len(), 1);
+    assert_eq!(result.links[0], "https://example.com/test");
+  }
+
+  #[test]
+  fn test_filter_links_non_utf8_robots_txt() {
+    let mut non_utf8_bytes = vec![0xFF, 0xFE];
+    non_utf8_bytes.extend_from_slice(b"User-agent: *\nDisallow: /blocked");
+    let non_utf8_string = String::from_utf8_lossy(&non_utf8_bytes).to_string();
+
+    let data = FilterLinksCall {
+      links: vec!["https://example.com/allowed".to_string()],
+      limit: Some(10),
+      options: CrawlFilterOptions {
+        includes: vec![],
+        excludes: vec![],
+        ignore_robots_txt: false,
+        robots_txt: non_utf8_string,
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        regex_on_full_url: false,
+        allow_backward_crawling: true,
+        allow_external_content_links: false,
+        allow_subdomains: false,
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    let result = _filter_links(data);
+    assert!(result.is_ok());
+    let result = result.unwrap();
+    assert_eq!(result.links.len(), 1);
+    assert_eq!(result.links[0], "https://example.com/allowed");
+  }
+
+  #[test]
+  fn test_filter_links_char_boundary_issue() {
+    let problematic_content = "User-agent: *\nDisallow: /\u{a0}test";
+
+    let data = FilterLinksCall {
+      links: vec!["https://example.com/test".to_string()],
+      limit: Some(10),
+      options: CrawlFilterOptions {
+        includes: vec![],
+        excludes: vec![],
+        ignore_robots_txt: false,
+        robots_txt: problematic_content.to_string(),
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        regex_on_full_url: false,
+        allow_backward_crawling: true,
+        allow_external_content_links: false,
+        allow_subdomains: false,
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    let result = _filter_links(data);
+    assert!(result.is_ok());
+    let result = result.unwrap();
+    assert_eq!(result.links.len(), 1);
+    assert_eq!(result.links[0], "https://example.com/test");
+  }
+
+  #[test]
+  fn test_filter_links_robots_user_agents_priority() {
+    let robots_txt = "User-agent: *\nDisallow: /all\n\nUser-agent: MyBot\nDisallow: /mybot\n";
+    let call = |agents: Option<Vec<&str>>| FilterLinksCall {
+      links: vec![
+        "https://example.com/all".to_string(),
+        "https://example.com/mybot".to_string(),
+      ],
+      limit: Some(10),
+      options: CrawlFilterOptions {
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        allow_backward_crawling: true,
+        robots_txt: robots_txt.to_string(),
+        robots_user_agents: agents.map(|a| a.iter().map(|s| s.to_string()).collect()),
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    let result = _filter_links(call(Some(vec!["OtherBot", "MyBot"]))).unwrap();
+    assert_eq!(result.robots_user_agent.as_deref(), Some("MyBot"));
+    assert_eq!(result.links, vec!["https://example.com/all"]);
+    assert_eq!(
+      result
+        .denial_reasons
+        .get("https://example.com/mybot")
+        .unwrap(),
+      "ROBOTS_TXT"
+    );
+
+    let result = _filter_links(call(None)).unwrap();
+    assert_eq!(result.robots_user_agent.as_deref(), Some("*"));
+    assert_eq!(result.links, vec!["https://example.com/mybot"]);
+
+    let result = _filter_url(FilterUrlCall {
+      href: "/mybot".to_string(),
+      url: "https://example.com/".to_string(),
+      options: CrawlFilterOptions {
+        initial_url: "https://example.com".to_string(),
+        base_url: "https://example.com".to_string(),
+        robots_txt: robots_txt.to_string(),
+        robots_user_agents: Some(vec!["mybot".to_string()]),
+        ..Default::default()
+      },
+    })
+    .unwrap();
+    assert!(!result.allowed);
+    assert_eq!(result.denial_reason.as_deref(), Some("ROBOTS_TXT"));
+    assert_eq!(result.robots_user_agent.as_deref(), Some("mybot"));
+  }
+
+  #[test]
+  fn test_filter_links_allow_subdomains_with_include_paths() {
+    let data = FilterLinksCall {
+      links: vec![
+        "https://sub.example.com/pricing".to_string(),
+        "https://sub.example.com/blog".to_string(),
+        "https://other.example.com/pricing".to_string(),
+        "https://example.com/pricing".to_string(),
+      ],
+      limit: Some(10),
+      options: CrawlFilterOptions {
+        includes: vec!["^/pricing$".to_string()],
+        excludes: vec![],
+        ignore_robots_txt: true,
+        robots_txt: "".to_string(),
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        regex_on_full_url: false,
+        allow_backward_crawling: true,
+        allow_external_content_links: false,
+        allow_subdomains: true,
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    let result = _filter_links(data).unwrap();
+    // Should include only paths matching include on base or subdomains
+    assert_eq!(result.links.len(), 3);
+    assert!(result
+      .links
+      .contains(&"https://example.com/pricing".to_string()));
+    assert!(result
+      .links
+      .contains(&"https://sub.example.com/pricing".to_string()));
+    assert!(result
+      .links
+      .contains(&"https://other.example.com/pricing".to_string()));
+    // And should exclude blog due to includePaths
+    assert!(result
+      .denial_reasons
+      .contains_key("https://sub.example.com/blog"));
+    assert_eq!(
+      result
+        .denial_reasons
+        .get("https://sub.example.com/blog")
+        .unwrap(),
+      "INCLUDE_PATTERN"
+    );
+  }
+
+  #[test]
+  fn test_crawl_filter_matches_per_call_filters() {
+    let robots_txt = "User-agent: *\nDisallow: /blog/private\n";
+    let links = vec![
+      "https://example.com/blog/post".to_string(),
+      "https://example.com/blog/private".to_string(),
+      "https://example.com/private/page".to_string(),
+      "https://example.com/blog/a/b/c/d".to_string(),
+      "https://example.com/blog/logo.png".to_string(),
+      "https://example.com/blog/skip-me".to_string(),
+      "https://example.com/about".to_string(),
+      "https://twitter.com/example".to_string(),
+      "https://other.com/article".to_string(),
+      "mailto:hi@example.com".to_string(),
+    ];
+    let filter = CrawlFilter::new(CrawlFilterOptions {
+      max_depth: 3,
+      base_url: "https://example.com".to_string(),
+      initial_url: "https://example.com/blog".to_string(),
+      excludes: vec!["skip".to_string(), "(".to_string()],
+      robots_txt: robots_txt.to_string(),
+      ..Default::default()
+    })
+    .unwrap();
+
+    let expected = _filter_links(FilterLinksCall {
+      links: links.clone(),
+      limit: Some(10),
+      options: CrawlFilterOptions {
+        max_depth: 3,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com/blog".to_string(),
+        excludes: vec!["skip".to_string(), "(".to_string()],
+        robots_txt: robots_txt.to_string(),
+        ..Default::default()
+      },
+      ..Default::default()
+    })
+    .unwrap();
+
+    // The compiled filter is reusable across pages.
+    for _ in 0..2 {
+      let result = filter
+        .inner
+        .filter_links(links.clone(), Some(10), &FilterLinksPage::default());
+      assert_eq!(result.links, expected.links);
+      assert_eq!(result.denial_reasons, expected.denial_reasons);
+      assert_eq!(result.robots_user_agent.as_deref(), Some("*"));
+    }
+    assert_eq!(expected.links, vec!["https://example.com/blog/post"]);
+    assert_eq!(
+      expected.denial_reasons["https://example.com/private/page"],
+      "BACKWARD_CRAWLING"
+    );
+    assert_eq!(
+      expected.denial_reasons["https://example.com/blog/private"],
+      "ROBOTS_TXT"
+    );
+    assert_eq!(
+      expected.denial_reasons["https://example.com/blog/a/b/c/d"],
+      "DEPTH_LIMIT"
+    );
+
+    assert_eq!(
+      filter
+        .inner
+        .filter_links(links, Some(0), &FilterLinksPage::default())
+        .links
+        .len(),
+      0
+    );
+
+    let result = filter
+      .inner
+      .filter_url("/blog/private/x", "https://example.com/");
+    assert_eq!(result.denial_reason.as_deref(), Some("ROBOTS_TXT"));
+    let result = filter.inner.filter_url("post", "https://example.com/blog/");
+    assert!(result.allowed);
+    assert_eq!(result.url.as_deref(), Some("https://example.com/blog/post"));
+    let result = filter
+      .inner
+      .filter_url("https://other.com/", "https://example.com/");
+    assert_eq!(result.denial_reason.as_deref(), Some("EXTERNAL_LINK"));
+
+    assert!(CrawlFilter::new(CrawlFilterOptions {
+      base_url: "not a url".to_string(),
+      initial_url: "https://example.com".to_string(),
+      ..Default::default()
+    })
+    .is_err());
+  }
+
+  #[test]
+  fn test_invalid_patterns_are_reported() {
+    let result = _validate_crawl_patterns(ValidatePatternsCall {
+      includes: vec!["^/blog/.*".to_string(), "/docs/(guide".to_string()],
+      excludes: vec!["[z-a]".to_string()],
+      ..Default::default()
+    })
+    .unwrap();
+    assert!(!result.valid);
+    assert_eq!(result.errors.len(), 2);
+
+    let include_error = &result.errors[0];
+    assert_eq!(include_error.list, "includes");
+    assert_eq!(include_error.index, 1);
+    assert_eq!(include_error.message, "unclosed group");
+    assert_eq!((include_error.start, include_error.end), (Some(6), Some(7)));
+
+    let exclude_error = &result.errors[1];
+    assert_eq!(exclude_error.list, "excludes");
+    assert_eq!(exclude_error.index, 0);
+    assert_eq!((exclude_error.start, exclude_error.end), (Some(1), Some(4)));
+
+    assert!(
+      _validate_crawl_patterns(ValidatePatternsCall {
+        includes: vec!["^/blog/.*".to_string()],
+        ..Default::default()
+      })
+      .unwrap()
+      .valid
+    );
+
+    let call = |strict_patterns| FilterLinksCall {
+      links: vec!["https://example.com/about".to_string()],
+      options: CrawlFilterOptions {
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        includes: vec!["/docs/(guide".to_string()],
+        strict_patterns,
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    // Lenient mode drops the bad include, so nothing is filtered out.
+    let result = _filter_links(call(None)).unwrap();
+    assert_eq!(result.links, vec!["https://example.com/about"]);
+
+    let err = _filter_links(call(Some(true))).err().unwrap();
+    assert!(err.contains("includes pattern #0"), "{err}");
+    assert!(err.contains("at offset 6"), "{err}");
+  }
+
+  #[test]
+  fn test_glob_to_regex() {
+    let glob = |pattern: &str, path: &str| {
+      let compiled = compile_pattern(pattern, PatternSyntax::Glob).ok().unwrap();
+      compiled.regex.is_match(path)
+    };
+
+    assert!(glob("/blog/**", "/blog"));
+    assert!(glob("/blog/**", "/blog/a/b"));
+    assert!(!glob("/blog/**", "/blogroll"));
+    assert!(glob("/docs/*.html", "/docs/intro.html"));
+    assert!(!glob("/docs/*.html", "/docs/guide/intro.html"));
+    assert!(glob("/docs/**/*.html", "/docs/intro.html"));
+    assert!(glob("/docs/**/*.html", "/docs/guide/intro.html"));
+    assert!(glob("*.pdf", "/files/report.pdf"));
+    assert!(glob("/v?/api", "/v2/api/users"));
+    assert!(glob("/[a-c]at", "/bat"));
+    assert!(!glob("/[!a-c]at", "/bat"));
+    assert!(glob("/pricing", "/pricing/enterprise"));
+    assert!(!glob("/pricing", "/pricing-old"));
+    assert!(glob("/a+b", "/a+b"));
+
+    let error = compile_pattern("!/docs/[abc", PatternSyntax::Glob)
+      .err()
+      .unwrap();
+    assert_eq!(error.message, "unclosed character class");
+    assert_eq!(error.span, Some((7, 11)));
+  }
+
+  #[test]
+  fn test_filter_links_glob_patterns() {
+    let call = |includes: Vec<&str>, excludes: Vec<&str>| FilterLinksCall {
+      links: vec![
+        "https://example.com/blog".to_string(),
+        "https://example.com/blog/post".to_string(),
+        "https://example.com/blog/drafts/wip".to_string(),
+        "https://example.com/docs/intro.html".to_string(),
+        "https://example.com/about".to_string(),
+      ],
+      options: CrawlFilterOptions {
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        allow_backward_crawling: true,
+        includes: includes.into_iter().map(String::from).collect(),
+        excludes: excludes.into_iter().map(String::from).collect(),
+        pattern_syntax: Some("glob".to_string()),
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    let result = _filter_links(call(vec!["/blog/**", "!/blog/drafts/**"], vec![])).unwrap();
+    assert_eq!(
+      result.links,
+      vec!["https://example.com/blog", "https://example.com/blog/post"]
+    );
+    assert_eq!(
+      result.denial_reasons["https://example.com/blog/drafts/wip"],
+      "INCLUDE_PATTERN"
+    );
+
+    let result = _filter_links(call(vec![], vec!["/blog/**", "!/blog/post"])).unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://example.com/blog/post",
+        "https://example.com/docs/intro.html",
+        "https://example.com/about",
+      ]
+    );
+
+    // A list of only negations matches everything else.
+    let result = _filter_links(call(vec![], vec!["!/docs/*.html"])).unwrap();
+    assert_eq!(result.links, vec!["https://example.com/docs/intro.html"]);
+
+    let mut data = call(vec![], vec![]);
+    data.options.pattern_syntax = Some("wildcard".to_string());
+    let err = _filter_links(data).err().unwrap();
+    assert!(err.contains("Unknown pattern syntax 'wildcard'"), "{err}");
+
+    // Root-anchored globs stay on the crawled site; external links are tested as `//host/path`.
+    let external = |excludes: Vec<&str>| {
+      let mut data = call(vec![], excludes);
+      data.links = vec![
+        "https://other.com/blog/x".to_string(),
+        "https://example.com/blog/x".to_string(),
+      ];
+      data.options.allow_external_content_links = true;
+      _filter_links(data).unwrap()
+    };
+    let result = external(vec!["/blog/**"]);
+    assert_eq!(result.links, vec!["https://other.com/blog/x"]);
+    let result = external(vec!["//other.com/**"]);
+    assert_eq!(result.links, vec!["https://example.com/blog/x"]);
+    let denial = &result.denials["https://other.com/blog/x"];
+    assert_eq!(denial.tested.as_deref(), Some("url"));
+    assert_eq!(denial.tested_value.as_deref(), Some("//other.com/blog/x"));
+    let result = external(vec!["**/blog/**"]);
+    assert!(result.links.is_empty());
+
+    let result = _filter_url(FilterUrlCall {
+      href: "/blog/drafts/wip".to_string(),
+      url: "https://example.com/".to_string(),
+      options: CrawlFilterOptions {
+        initial_url: "https://example.com".to_string(),
+        base_url: "https://example.com".to_string(),
+        excludes: vec!["/blog/drafts/".to_string()],
+        pattern_syntax: Some("glob".to_string()),
+        ..Default::default()
+      },
+    })
+    .unwrap();
+    assert_eq!(result.denial_reason.as_deref(), Some("EXCLUDE_PATTERN"));
+  }
+
+  #[test]
+  fn test_filter_links_deny_list_overrides() {
+    let call = |file_extensions, social_media_domains| FilterLinksCall {
+      links: vec![
+        "https://example.com/report.xlsx".to_string(),
+        "https://example.com/deck.pptx".to_string(),
+        "https://example.com/archive.tar".to_string(),
+        "https://github.com/firecrawl/firecrawl".to_string(),
+        "https://twitter.com/firecrawl".to_string(),
+        "https://example.social/profile".to_string(),
+      ],
+      options: CrawlFilterOptions {
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        allow_external_content_links: true,
+        file_extensions,
+        social_media_domains,
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    let result = _filter_links(call(None, None)).unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://example.com/archive.tar",
+        "https://example.social/profile"
+      ]
+    );
+    assert_eq!(
+      result.denial_reasons["https://github.com/firecrawl/firecrawl"],
+      "SOCIAL_MEDIA"
+    );
+
+    let result = _filter_links(call(
+      Some(DenyListOverride {
+        add: Some(vec!["tar".to_string()]),
+        remove: Some(vec![".XLSX".to_string(), "pptx".to_string()]),
+      }),
+      Some(DenyListOverride {
+        add: Some(vec!["example.social".to_string()]),
+        remove: Some(vec!["github.com".to_string()]),
+      }),
+    ))
+    .unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://example.com/report.xlsx",
+        "https://example.com/deck.pptx",
+        "https://github.com/firecrawl/firecrawl",
+      ]
+    );
+    assert_eq!(
+      result.denial_reasons["https://example.com/archive.tar"],
+      "FILE_TYPE"
+    );
+    assert_eq!(
+      result.denial_reasons["https://example.social/profile"],
+      "SOCIAL_MEDIA"
+    );
+    assert_eq!(
+      result.denial_reasons["https://twitter.com/firecrawl"],
+      "SOCIAL_MEDIA"
+    );
+  }
+
+  #[test]
+  fn test_filter_links_allowed_categories() {
+    let call = |allowed_categories: Option<Vec<&str>>| FilterLinksCall {
+      links: vec![
+        "https://example.com/guide".to_string(),
+        "https://example.com/download?id=3".to_string(),
+        "https://example.com/deck.pptx".to_string(),
+        "https://example.com/logo.png".to_string(),
+      ],
+      options: CrawlFilterOptions {
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        allowed_categories: allowed_categories
+          .map(|categories| categories.into_iter().map(String::from).collect()),
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    let result = _filter_links(call(None)).unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://example.com/guide",
+        "https://example.com/download?id=3"
+      ]
+    );
+
+    let result = _filter_links(call(Some(vec!["page"]))).unwrap();
+    assert_eq!(result.links, vec!["https://example.com/guide"]);
+    assert_eq!(
+      result.denial_reasons["https://example.com/download?id=3"],
+      "FILE_TYPE"
+    );
+
+    let result = _filter_links(call(Some(vec!["page", "Document"]))).unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://example.com/guide",
+        "https://example.com/download?id=3",
+        "https://example.com/deck.pptx",
+      ]
+    );
+
+    // Explicit extension changes win over the category.
+    let mut data = call(Some(vec!["page", "document"]));
+    data.options.file_extensions = Some(DenyListOverride {
+      add: Some(vec!["pptx".to_string()]),
+      remove: Some(vec![".PNG".to_string()]),
+    });
+    let result = _filter_links(data).unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://example.com/guide",
+        "https://example.com/download?id=3",
+        "https://example.com/logo.png",
+      ]
+    );
+    assert_eq!(
+      result.denial_reasons["https://example.com/deck.pptx"],
+      "FILE_TYPE"
+    );
+
+    let err = _filter_links(call(Some(vec!["video"]))).err().unwrap();
+    assert!(err.contains("Unknown link category 'video'"), "{err}");
+  }
+
+  #[test]
+  fn test_filter_links_host_scope() {
+    let call = |base_url: &str, host_scope: Option<&str>, allowed_hosts: Option<Vec<&str>>| {
+      FilterLinksCall {
+        links: vec![
+          "https://WWW.Example.co.uk/a".to_string(),
+          "https://example.co.uk./b".to_string(),
+          "https://blog.example.co.uk/c".to_string(),
+          "https://other.co.uk/d".to_string(),
+          "https://bücher.example/e".to_string(),
+        ],
+        options: CrawlFilterOptions {
+          max_depth: 10,
+          base_url: base_url.to_string(),
+          initial_url: base_url.to_string(),
+          host_scope: host_scope.map(String::from),
+          allowed_hosts: allowed_hosts.map(|hosts| hosts.into_iter().map(String::from).collect()),
+          ..Default::default()
+        },
+        ..Default::default()
+      }
+    };
+
+    let result = _filter_links(call("https://example.co.uk", None, None)).unwrap();
+    assert_eq!(
+      result.links,
+      vec!["https://WWW.Example.co.uk/a", "https://example.co.uk./b"]
+    );
+    assert_eq!(
+      result.denial_reasons["https://blog.example.co.uk/c"],
+      "EXTERNAL_LINK"
+    );
+
+    let result = _filter_links(call("https://example.co.uk", Some("exact"), None)).unwrap();
+    assert_eq!(result.links, vec!["https://example.co.uk./b"]);
+
+    let result = _filter_links(call(
+      "https://www.example.co.uk",
+      Some("registrable_domain"),
+      Some(vec!["BÜCHER.example."]),
+    ))
+    .unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://WWW.Example.co.uk/a",
+        "https://example.co.uk./b",
+        "https://blog.example.co.uk/c",
+        "https://bücher.example/e",
+      ]
+    );
+    assert_eq!(
+      result.denial_reasons["https://other.co.uk/d"],
+      "EXTERNAL_LINK"
+    );
+
+    let err = _filter_links(call("https://example.co.uk", Some("site"), None))
+      .err()
+      .unwrap();
+    assert!(err.contains("Unknown host scope 'site'"), "{err}");
+  }
+
+  #[test]
+  fn test_filter_links_allowed_hosts_wildcards() {
+    let call = |allowed_hosts: Vec<&str>| FilterLinksCall {
+      links: vec![
+        "https://docs.acme.com/guide/start".to_string(),
+        "https://acme.readme.io/guide/api".to_string(),
+        "https://www.acme.readme.io/guide/sdk".to_string(),
+        "https://help.acme.com/guide/faq".to_string(),
+        "https://eu.help.acme.com/guide/faq".to_string(),
+        "https://acme-beta.readme.io/guide/new".to_string(),
+        "https://acme.readme.io/changelog".to_string(),
+        "https://acme.readme.io/guide/a/b/c".to_string(),
+        "https://other.readme.io/guide/x".to_string(),
+      ],
+      options: CrawlFilterOptions {
+        max_depth: 3,
+        base_url: "https://docs.acme.com".to_string(),
+        initial_url: "https://docs.acme.com/guide".to_string(),
+        excludes: vec!["/faq$".to_string()],
+        allowed_hosts: Some(allowed_hosts.into_iter().map(String::from).collect()),
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    let result = _filter_links(call(vec![
+      "acme.readme.io",
+      "*.acme.com",
+      "acme-*.readme.io",
+    ]))
+    .unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://docs.acme.com/guide/start",
+        "https://acme.readme.io/guide/api",
+        "https://www.acme.readme.io/guide/sdk",
+        "https://acme-beta.readme.io/guide/new",
+      ]
     );
+    // Allowed hosts get the same checks as the base host.
     assert_eq!(
-      sitemapindex.sitemap[1].loc[0],
-      "https://example.com/sitemap2.xml"
+      result.denial_reasons["https://help.acme.com/guide/faq"],
+      "EXCLUDE_PATTERN"
+    );
+    assert_eq!(
+      result.denial_reasons["https://acme.readme.io/changelog"],
+      "BACKWARD_CRAWLING"
+    );
+    assert_eq!(
+      result.denial_reasons["https://acme.readme.io/guide/a/b/c"],
+      "DEPTH_LIMIT"
     );
+    assert_eq!(
+      result.denial_reasons["https://other.readme.io/guide/x"],
+      "EXTERNAL_LINK"
+    );
+
+    let filter = CrawlFilter::new(CrawlFilterOptions {
+      base_url: "https://docs.acme.com".to_string(),
+      initial_url: "https://docs.acme.com".to_string(),
+      allowed_hosts: Some(vec!["*.acme.com".to_string()]),
+      ..Default::default()
+    })
+    .unwrap();
+    // `*.` only matches subdomains, so the apex stays external.
+    let result = filter
+      .inner
+      .filter_url("https://acme.com/", "https://docs.acme.com/");
+    assert_eq!(result.denial_reason.as_deref(), Some("EXTERNAL_LINK"));
+    let result = filter
+      .inner
+      .filter_url("https://a.b.acme.com/", "https://docs.acme.com/");
+    assert!(result.allowed);
   }
 
   #[test]
-  fn test_parse_sitemap_xml_invalid_root() {
-    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
-<invalid xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
-  <url>
-    <loc>https://example.com/page1</loc>
-  </url>
-</invalid>"#;
+  fn test_filter_links_discovery_depth() {
+    let call = |parent_depth| FilterLinksCall {
+      links: vec![
+        "https://example.com/p?id=1".to_string(),
+        "https://example.com/a/b/c/d/e".to_string(),
+      ],
+      options: CrawlFilterOptions {
+        max_depth: 10,
+        max_discovery_depth: Some(2),
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        ..Default::default()
+      },
+      page: Some(FilterLinksPage {
+        parent_depth,
+        ..Default::default()
+      }),
+      ..Default::default()
+    };
 
-    let result = _parse_sitemap_xml(xml_content);
-    assert!(result.is_err());
-    assert!(result.unwrap_err().contains("Invalid sitemap format"));
-  }
+    let result = _filter_links(call(Some(1))).unwrap();
+    assert_eq!(result.links.len(), 2);
+    assert_eq!(result.link_depth, Some(2));
 
-  #[test]
-  fn test_parse_sitemap_xml_malformed() {
-    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
-<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
-  <url>
-    <loc>https://example.com/page1</loc>
-  </url>
-</urlset"#; // Missing closing >
+    let result = _filter_links(call(Some(2))).unwrap();
+    assert!(result.links.is_empty());
+    assert_eq!(
+      result.denial_reasons["https://example.com/p?id=1"],
+      "DISCOVERY_DEPTH_LIMIT"
+    );
+    assert_eq!(result.link_depth, Some(3));
 
-    let result = _parse_sitemap_xml(xml_content);
-    assert!(result.is_err());
+    let err = _filter_links(call(None)).err().unwrap();
+    assert!(err.contains("requires parent_depth"), "{err}");
+
+    let mut data = call(Some(0));
+    data.options.max_depth = 3;
+    let result = _filter_links(data).unwrap();
+    assert_eq!(
+      result.denial_reasons["https://example.com/a/b/c/d/e"],
+      "DEPTH_LIMIT"
+    );
   }
 
   #[test]
-  fn test_process_sitemap_urlset() {
-    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
-<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
-  <url>
-    <loc>https://example.com/page1</loc>
-  </url>
-  <url>
-    <loc>https://example.com/sitemap2.xml</loc>
-  </url>
-  <url>
-    <loc>https://example.com/image.png</loc>
-  </url>
-</urlset>"#;
+  fn test_filter_links_structured_denials() {
+    let result = _filter_links(FilterLinksCall {
+      links: vec![
+        "https://example.com/pricing?plan=pro".to_string(),
+        "https://example.com/blog/private/post".to_string(),
+        "https://example.com/blog/ok".to_string(),
+        "https://example.com/docs".to_string(),
+      ],
+      options: CrawlFilterOptions {
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        excludes: vec!["^/a$".to_string(), "(".to_string(), "/pricing".to_string()],
+        includes: vec!["/blog".to_string(), "/pricing".to_string()],
+        robots_txt: "User-agent: *\nAllow: /\nDisallow: /blog/private\n".to_string(),
+        ..Default::default()
+      },
+      ..Default::default()
+    })
+    .unwrap();
+    assert_eq!(result.links, vec!["https://example.com/blog/ok"]);
+    assert_eq!(result.denials.len(), result.denial_reasons.len());
+    for (link, reason) in &result.denial_reasons {
+      assert_eq!(&result.denials[link].code, reason);
+    }
 
-    let result = _process_sitemap(xml_content).unwrap();
-    assert_eq!(result.instructions.len(), 2);
+    // The invalid pattern is skipped but still counts towards the index.
+    let exclude = &result.denials["https://example.com/pricing?plan=pro"];
+    assert_eq!(exclude.code, "EXCLUDE_PATTERN");
+    assert_eq!(exclude.pattern_index, Some(2));
+    assert_eq!(exclude.pattern.as_deref(), Some("/pricing"));
+    assert_eq!(exclude.tested.as_deref(), Some("path"));
+    assert_eq!(exclude.tested_value.as_deref(), Some("/pricing"));
 
-    let recurse_instruction = result
-      .instructions
-      .iter()
-      .find(|i| i.action == "recurse")
-      .unwrap();
-    assert_eq!(recurse_instruction.urls.len(), 1);
+    let robots = &result.denials["https://example.com/blog/private/post"];
+    assert_eq!(robots.code, "ROBOTS_TXT");
     assert_eq!(
-      recurse_instruction.urls[0],
-      "https://example.com/sitemap2.xml"
+      robots.url.as_deref(),
+      Some("https://example.com/blog/private/post")
+    );
+    let rule = robots.robots_rule.as_ref().unwrap();
+    assert_eq!(
+      (rule.directive.as_str(), rule.pattern.as_str(), rule.line),
+      ("disallow", "/blog/private", 3)
     );
 
-    let process_instruction = result
-      .instructions
-      .iter()
-      .find(|i| i.action == "process")
+    let include = &result.denials["https://example.com/docs"];
+    assert_eq!(include.code, "INCLUDE_PATTERN");
+    assert_eq!(include.pattern_index, None);
+    assert_eq!(include.tested_value.as_deref(), Some("/docs"));
+
+    let result = _filter_url(FilterUrlCall {
+      href: "https://example.com/pricing".to_string(),
+      url: "https://example.com".to_string(),
+      options: CrawlFilterOptions {
+        initial_url: "https://example.com".to_string(),
+        base_url: "https://example.com".to_string(),
+        excludes: vec!["pricing$".to_string()],
+        ..Default::default()
+      },
+    })
+    .unwrap();
+    assert_eq!(result.denial_reason.as_deref(), Some("EXCLUDE_PATTERN"));
+    let denial = result.denial.unwrap();
+    assert_eq!(denial.pattern_index, Some(0));
+    assert_eq!(denial.url.as_deref(), Some("https://example.com/pricing"));
+  }
+
+  #[test]
+  fn test_filter_links_robots_denials_match_parse_robots_txt() {
+    let robots_txt = "User-agent: *\nDisallow: /*.pdf$\nDisallow: /private\n\
+                      Allow: /private/press\n\nUser-agent: MyBot\nDisallow: /mybot\n";
+    let links: Vec<String> = [
+      "https://example.com/files/a.pdf",
+      "https://example.com/files/a.pdf?download=1",
+      "https://example.com/private/notes",
+      "https://example.com/private/press/launch",
+      "https://example.com/mybot",
+    ]
+    .iter()
+    .map(|link| link.to_string())
+    .collect();
+
+    for (agents, denied) in [(None, 2), (Some(vec!["MyBot".to_string()]), 1)] {
+      let result = _filter_links(FilterLinksCall {
+        links: links.clone(),
+        options: CrawlFilterOptions {
+          max_depth: 10,
+          base_url: "https://example.com".to_string(),
+          initial_url: "https://example.com".to_string(),
+          robots_txt: robots_txt.to_string(),
+          robots_user_agents: agents.clone(),
+          ..Default::default()
+        },
+        ..Default::default()
+      })
       .unwrap();
-    assert_eq!(process_instruction.urls.len(), 1);
-    assert_eq!(process_instruction.urls[0], "https://example.com/page1");
+      let analysis = _parse_robots_txt(ParseRobotsTxtCall {
+        robots_txt: robots_txt.to_string(),
+        user_agents: agents,
+        urls: Some(links.clone()),
+      });
+
+      assert_eq!(result.robots_user_agent, analysis.matched_user_agent);
+      assert_eq!(result.denials.len(), denied);
+      for verdict in analysis.url_verdicts {
+        let denial = result.denials.get(&verdict.url);
+        assert_eq!(denial.is_none(), verdict.allowed, "{}", verdict.url);
+        if let Some(denial) = denial {
+          let rule = denial.robots_rule.as_ref().unwrap();
+          let expected = verdict.rule.unwrap();
+          assert_eq!(
+            (rule.directive.as_str(), rule.line),
+            (expected.directive.as_str(), expected.line)
+          );
+        }
+      }
+    }
   }
 
   #[test]
-  fn test_process_sitemap_sitemapindex() {
-    let xml_content = r#"<?xml version="1.0" encoding="UTF-8"?>
-<sitemapindex xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">
-  <sitemap>
-    <loc>https://example.com/sitemap1.xml</loc>
-  </sitemap>
-  <sitemap>
-    <loc>https://example.com/sitemap2.xml</loc>
-  </sitemap>
-</sitemapindex>"#;
+  fn test_robots_directives() {
+    let meta = |name: &str, content: &str| (name.to_string(), content.to_string());
 
-    let result = _process_sitemap(xml_content).unwrap();
-    assert_eq!(result.instructions.len(), 1);
-    assert_eq!(result.instructions[0].action, "recurse");
-    assert_eq!(result.instructions[0].urls.len(), 2);
+    let directives = robots_directives(
+      &[
+        meta("robots", "NOINDEX, follow"),
+        meta("googlebot", "nofollow"),
+        meta("description", "noarchive"),
+      ],
+      &[],
+      None,
+    );
     assert_eq!(
-      result.instructions[0].urls[0],
-      "https://example.com/sitemap1.xml"
+      directives,
+      PageRobotsDirectives {
+        noindex: true,
+        nofollow: false,
+        noarchive: false,
+      }
+    );
+
+    let directives = robots_directives(
+      &[meta("FirecrawlAgent", "noarchive")],
+      &[
+        "googlebot: noindex".to_string(),
+        "unavailable_after: 2030-01-01".to_string(),
+        "firecrawlagent: none".to_string(),
+      ],
+      None,
     );
     assert_eq!(
-      result.instructions[0].urls[1],
-      "https://example.com/sitemap2.xml"
+      directives,
+      PageRobotsDirectives {
+        noindex: true,
+        nofollow: true,
+        noarchive: true,
+      }
+    );
+
+    let agents = vec!["googlebot".to_string()];
+    let directives = robots_directives(&[], &["googlebot: noindex".to_string()], Some(&agents));
+    assert!(directives.noindex);
+
+    // Value directives don't read as agent prefixes, and a prefix lasts until the next one.
+    let directives = robots_directives(
+      &[],
+      &[
+        "noindex, max-snippet:50".to_string(),
+        "googlebot: noarchive, max-image-preview: large, FirecrawlAgent: nofollow".to_string(),
+      ],
+      None,
+    );
+    assert_eq!(
+      directives,
+      PageRobotsDirectives {
+        noindex: true,
+        nofollow: true,
+        noarchive: false,
+      }
+    );
+
+    // An unknown `key:value` directive isn't taken for an agent that scopes the rest.
+    let directives = robots_directives(
+      &[],
+      &[
+        "max-future-preview: 5, noindex".to_string(),
+        "x-robots:1, nofollow".to_string(),
+      ],
+      None,
     );
+    assert!(directives.noindex);
+    assert!(directives.nofollow);
   }
 
   #[test]
-  fn test_filter_links_normal_robots_txt() {
-    let data = FilterLinksCall {
+  fn test_filter_links_nofollow() {
+    let call = |respect_nofollow, nofollow| FilterLinksCall {
       links: vec![
-        "https://example.com/allowed".to_string(),
-        "https://example.com/disallowed".to_string(),
+        "https://example.com/a".to_string(),
+        "https://example.com/b".to_string(),
       ],
-      limit: Some(10),
-      includes: vec![],
-      excludes: vec![],
-      ignore_robots_txt: false,
-      robots_txt: "User-agent: *\nDisallow: /disallowed".to_string(),
-      max_depth: 10,
-      base_url: "https://example.com".to_string(),
-      initial_url: "https://example.com".to_string(),
-      regex_on_full_url: false,
-      allow_backward_crawling: true,
-      allow_external_content_links: false,
-      allow_subdomains: false,
+      options: CrawlFilterOptions {
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        respect_nofollow,
+        ..Default::default()
+      },
+      page: Some(FilterLinksPage {
+        nofollow,
+        link_hints: Some(HashMap::from([(
+          "https://example.com/b".to_string(),
+          LinkHint {
+            rel: Some(vec!["ugc".to_string(), "NoFollow".to_string()]),
+            ..Default::default()
+          },
+        )])),
+        ..Default::default()
+      }),
+      ..Default::default()
     };
 
-    let result = _filter_links(data).unwrap();
-    assert_eq!(result.links.len(), 1);
-    assert_eq!(result.links[0], "https://example.com/allowed");
-    assert!(result
-      .denial_reasons
-      .contains_key("https://example.com/disallowed"));
     assert_eq!(
-      result
-        .denial_reasons
-        .get("https://example.com/disallowed")
-        .unwrap(),
-      "ROBOTS_TXT"
+      _filter_links(call(None, Some(true))).unwrap().links.len(),
+      2
     );
+
+    let result = _filter_links(call(Some(true), None)).unwrap();
+    assert_eq!(result.links, vec!["https://example.com/a"]);
+    assert_eq!(result.denial_reasons["https://example.com/b"], "NOFOLLOW");
+
+    let result = _filter_links(call(Some(true), Some(true))).unwrap();
+    assert!(result.links.is_empty());
+    assert_eq!(result.denial_reasons["https://example.com/a"], "NOFOLLOW");
   }
 
   #[test]
-  fn test_filter_links_malformed_robots_txt() {
-    let data = FilterLinksCall {
-      links: vec!["https://example.com/test".to_string()],
-      limit: Some(10),
-      includes: vec![],
-      excludes: vec![],
-      ignore_robots_txt: false,
-      robots_txt: "Invalid robots.txt content with \x00 null bytes and malformed syntax"
-        .to_string(),
-      max_depth: 10,
-      base_url: "https://example.com".to_string(),
-      initial_url: "https://example.com".to_string(),
-      regex_on_full_url: false,
-      allow_backward_crawling: true,
-      allow_external_content_links: false,
-      allow_subdomains: false,
+  fn test_filter_links_fragment_policy() {
+    let call = |fragment_policy: Option<&str>, fragment_pattern: Option<&str>| FilterLinksCall {
+      links: vec![
+        "https://example.com/app#!/settings".to_string(),
+        "https://example.com/app#/inbox".to_string(),
+        "https://example.com/docs#section/intro".to_string(),
+        "https://example.com/docs#install".to_string(),
+        "https://example.com/docs".to_string(),
+      ],
+      options: CrawlFilterOptions {
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        fragment_policy: fragment_policy.map(str::to_string),
+        fragment_pattern: fragment_pattern.map(str::to_string),
+        ..Default::default()
+      },
+      ..Default::default()
     };
 
-    let result = _filter_links(data);
-    assert!(result.is_ok());
-    let result = result.unwrap();
-    assert_eq!(result.links.len(), 1);
-    assert_eq!(result.links[0], "https://example.com/test");
-  }
+    // The default heuristic keeps fragments with a slash and denies the rest.
+    let result = _filter_links(call(None, None)).unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://example.com/app#!/settings",
+        "https://example.com/app#/inbox",
+        "https://example.com/docs#section/intro",
+        "https://example.com/docs",
+      ]
+    );
+    assert_eq!(
+      result.denial_reasons["https://example.com/docs#install"],
+      "SECTION_LINK"
+    );
 
-  #[test]
-  fn test_filter_links_non_utf8_robots_txt() {
-    let mut non_utf8_bytes = vec![0xFF, 0xFE];
-    non_utf8_bytes.extend_from_slice(b"User-agent: *\nDisallow: /blocked");
-    let non_utf8_string = String::from_utf8_lossy(&non_utf8_bytes).to_string();
+    let result = _filter_links(call(Some("drop"), None)).unwrap();
+    assert_eq!(
+      result.links,
+      vec!["https://example.com/app", "https://example.com/docs"]
+    );
+    assert_eq!(
+      result.denial_reasons["https://example.com/app#/inbox"],
+      "DUPLICATE"
+    );
+    assert_eq!(
+      result.denial_reasons["https://example.com/docs"],
+      "DUPLICATE"
+    );
 
-    let data = FilterLinksCall {
-      links: vec!["https://example.com/allowed".to_string()],
-      limit: Some(10),
-      includes: vec![],
-      excludes: vec![],
-      ignore_robots_txt: false,
-      robots_txt: non_utf8_string,
-      max_depth: 10,
-      base_url: "https://example.com".to_string(),
-      initial_url: "https://example.com".to_string(),
-      regex_on_full_url: false,
-      allow_backward_crawling: true,
-      allow_external_content_links: false,
-      allow_subdomains: false,
-    };
+    let result = _filter_links(call(Some("hashbang"), None)).unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://example.com/app#!/settings",
+        "https://example.com/app",
+        "https://example.com/docs",
+      ]
+    );
 
-    let result = _filter_links(data);
-    assert!(result.is_ok());
-    let result = result.unwrap();
-    assert_eq!(result.links.len(), 1);
-    assert_eq!(result.links[0], "https://example.com/allowed");
-  }
+    let result = _filter_links(call(Some("routes"), None)).unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://example.com/app#!/settings",
+        "https://example.com/app#/inbox",
+        "https://example.com/docs",
+      ]
+    );
 
-  #[test]
-  fn test_filter_links_char_boundary_issue() {
-    let problematic_content = "User-agent: *\nDisallow: /\u{a0}test";
+    let result = _filter_links(call(None, Some("^section/"))).unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://example.com/app",
+        "https://example.com/docs#section/intro",
+        "https://example.com/docs",
+      ]
+    );
 
-    let data = FilterLinksCall {
-      links: vec!["https://example.com/test".to_string()],
-      limit: Some(10),
-      includes: vec![],
-      excludes: vec![],
-      ignore_robots_txt: false,
-      robots_txt: problematic_content.to_string(),
-      max_depth: 10,
-      base_url: "https://example.com".to_string(),
-      initial_url: "https://example.com".to_string(),
-      regex_on_full_url: false,
-      allow_backward_crawling: true,
-      allow_external_content_links: false,
-      allow_subdomains: false,
-    };
+    assert!(_filter_links(call(Some("pattern"), None)).is_err());
+    assert!(_filter_links(call(Some("drop"), Some("x"))).is_err());
+    assert!(_filter_links(call(Some("anchors"), None)).is_err());
 
-    let result = _filter_links(data);
-    assert!(result.is_ok());
-    let result = result.unwrap();
-    assert_eq!(result.links.len(), 1);
-    assert_eq!(result.links[0], "https://example.com/test");
+    let result = _filter_url(FilterUrlCall {
+      href: "/docs#install".to_string(),
+      url: "https://example.com".to_string(),
+      options: CrawlFilterOptions {
+        initial_url: "https://example.com".to_string(),
+        base_url: "https://example.com".to_string(),
+        fragment_policy: Some("drop".to_string()),
+        ..Default::default()
+      },
+    })
+    .unwrap();
+    assert_eq!(result.url.as_deref(), Some("https://example.com/docs"));
   }
 
   #[test]
-  fn test_filter_links_allow_subdomains_with_include_paths() {
-    let data = FilterLinksCall {
+  fn test_filter_links_preferred_locales() {
+    let alternates = |pairs: &[(&str, &str)]| {
+      pairs
+        .iter()
+        .map(|(hreflang, href)| SitemapAlternate {
+          hreflang: hreflang.to_string(),
+          href: href.to_string(),
+        })
+        .collect::<Vec<_>>()
+    };
+    let call = |preferred_locales: &[&str]| FilterLinksCall {
       links: vec![
-        "https://sub.example.com/pricing".to_string(),
-        "https://sub.example.com/blog".to_string(),
-        "https://other.example.com/pricing".to_string(),
-        "https://example.com/pricing".to_string(),
+        "/en/pricing".to_string(),
+        "/de/preise".to_string(),
+        "/fr/tarifs".to_string(),
+        "/de/blog".to_string(),
+        "/fr/blog".to_string(),
+        "/jobs".to_string(),
       ],
-      limit: Some(10),
-      includes: vec!["^/pricing$".to_string()],
-      excludes: vec![],
-      ignore_robots_txt: true,
-      robots_txt: "".to_string(),
-      max_depth: 10,
-      base_url: "https://example.com".to_string(),
-      initial_url: "https://example.com".to_string(),
-      regex_on_full_url: false,
-      allow_backward_crawling: true,
-      allow_external_content_links: false,
-      allow_subdomains: true,
+      options: CrawlFilterOptions {
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        preferred_locales: Some(preferred_locales.iter().map(|l| l.to_string()).collect()),
+        ..Default::default()
+      },
+      page: Some(FilterLinksPage {
+        locale_alternates: Some(vec![
+          alternates(&[
+            ("en-US", "https://example.com/en/pricing"),
+            ("de", "/de/preise"),
+            ("fr-FR", "/fr/tarifs"),
+          ]),
+          alternates(&[
+            ("x-default", "/fr/blog"),
+            ("de_DE", "/de/blog"),
+            ("fr", "/fr/blog"),
+          ]),
+        ]),
+        ..Default::default()
+      }),
+      ..Default::default()
     };
 
-    let result = _filter_links(data).unwrap();
-    // Should include only paths matching include on base or subdomains
-    assert_eq!(result.links.len(), 3);
-    assert!(result
-      .links
-      .contains(&"https://example.com/pricing".to_string()));
-    assert!(result
-      .links
-      .contains(&"https://sub.example.com/pricing".to_string()));
-    assert!(result
-      .links
-      .contains(&"https://other.example.com/pricing".to_string()));
-    // And should exclude blog due to includePaths
-    assert!(result
-      .denial_reasons
-      .contains_key("https://sub.example.com/blog"));
+    // `en` matches `en-US` on language; the blog has no English version, so `x-default` wins.
+    let result = _filter_links(call(&["en"])).unwrap();
+    assert_eq!(result.links, vec!["/en/pricing", "/fr/blog", "/jobs"]);
+    assert_eq!(result.denial_reasons["/de/preise"], "LOCALE_ALTERNATE");
     assert_eq!(
-      result
-        .denial_reasons
-        .get("https://sub.example.com/blog")
-        .unwrap(),
-      "INCLUDE_PATTERN"
+      result.denials["/de/blog"].pattern.as_deref(),
+      Some("https://example.com/fr/blog")
+    );
+
+    let result = _filter_links(call(&["de-DE", "en"])).unwrap();
+    assert_eq!(result.links, vec!["/de/preise", "/de/blog", "/jobs"]);
+
+    // A preferred alternate that isn't linked, or is denied, gives way to the next best.
+    let mut fallback = call(&["de-DE", "en"]);
+    fallback.links.retain(|link| link != "/de/preise");
+    fallback.options.excludes = vec!["^/de/blog".to_string()];
+    let result = _filter_links(fallback).unwrap();
+    assert_eq!(result.links, vec!["/en/pricing", "/fr/blog", "/jobs"]);
+    assert_eq!(result.denial_reasons["/de/blog"], "EXCLUDE_PATTERN");
+    assert_eq!(
+      result.denials["/fr/tarifs"].pattern.as_deref(),
+      Some("https://example.com/en/pricing")
     );
+
+    // Without preferred locales alternates are left alone.
+    let result = _filter_links(call(&[])).unwrap();
+    assert_eq!(result.links.len(), 6);
   }
 
   #[test]
@@ -0,0 +1,704 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::{
+  cmp::Ordering,
+  collections::{BinaryHeap, HashMap, HashSet},
+  sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+use tokio::task;
+
+use crate::crawler::{
+  url_host, CompiledCrawlFilter, CrawlFilterOptions, Denial, FilterLinksPage, FilterLinksResult,
+  DUPLICATE, TESTED_URL,
+};
+
+/// Bumped whenever the snapshot layout changes, so stale snapshots fail loudly.
+const SNAPSHOT_VERSION: u32 = 1;
+
+const DEFAULT_FALSE_POSITIVE_RATE: f64 = 0.001;
+
+/// Track seen URLs in a bloom filter, which takes about 1.8 bytes per expected URL at the
+/// default rate instead of the URLs themselves, at the cost of occasionally treating a new URL
+/// as seen.
+#[derive(Deserialize, Default)]
+#[napi(object)]
+pub struct BloomFilterOptions {
+  /// URLs the crawl is expected to see; the false positive rate rises beyond it.
+  pub expected_urls: u32,
+  /// Chance that an unseen URL is reported as seen. Defaults to 0.001.
+  pub false_positive_rate: Option<f64>,
+}
+
+#[derive(Deserialize, Default)]
+#[napi(object)]
+pub struct CrawlFrontierOptions {
+  pub filter: CrawlFilterOptions,
+  /// Use a bloom filter for seen URLs instead of an exact set.
+  pub bloom_filter: Option<BloomFilterOptions>,
+  /// URLs handed out by `pop` per host that haven't been `complete`d yet. Defaults to 1.
+  ///
+  /// With a `CrawlScheduler`, popped URLs go to its `schedule`, and this bounds how many of a
+  /// host's URLs are popped but not done, including those the scheduler defers. The
+  /// scheduler's `max_concurrency_per_host` bounds how many are fetched at once, so set this
+  /// at least as high, and `complete` each fetched URL on both.
+  pub max_in_flight_per_host: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[napi(object)]
+pub struct FrontierEntry {
+  pub url: String,
+  pub host: String,
+  /// Hop distance from the start URL.
+  pub depth: u32,
+  /// Score from the filter's `scoring` options, or 0 without them.
+  pub score: f64,
+}
+
+/// A queued entry, ordered best-first: higher score, then shallower, then queued earlier.
+#[derive(Serialize, Deserialize)]
+struct QueuedEntry {
+  entry: FrontierEntry,
+  seq: u64,
+}
+
+impl Ord for QueuedEntry {
+  fn cmp(&self, other: &Self) -> Ordering {
+    self
+      .entry
+      .score
+      .total_cmp(&other.entry.score)
+      .then_with(|| other.entry.depth.cmp(&self.entry.depth))
+      .then_with(|| other.seq.cmp(&self.seq))
+  }
+}
+
+impl PartialOrd for QueuedEntry {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl PartialEq for QueuedEntry {
+  fn eq(&self, other: &Self) -> bool {
+    self.cmp(other) == Ordering::Equal
+  }
+}
+
+impl Eq for QueuedEntry {}
+
+/// 64-bit FNV-1a, started from `basis` so one key can give independent hashes.
+fn fnv1a(bytes: &[u8], basis: u64) -> u64 {
+  bytes.iter().fold(basis, |hash, byte| {
+    (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+  })
+}
+
+#[derive(Serialize, Deserialize)]
+struct BloomFilter {
+  bits: Vec<u64>,
+  hashes: u32,
+}
+
+impl BloomFilter {
+  fn new(expected: u32, false_positive_rate: f64) -> Self {
+    let expected = expected.max(1) as f64;
+    let rate = false_positive_rate.clamp(1e-9, 0.5);
+    let ln2 = std::f64::consts::LN_2;
+    let bits = (-expected * rate.ln() / (ln2 * ln2)).ceil().max(64.0);
+    let hashes = (bits / expected * ln2).round().clamp(1.0, 32.0) as u32;
+    Self {
+      bits: vec![0; (bits as usize).div_ceil(64)],
+      hashes,
+    }
+  }
+
+  /// Bit positions for `key`, by double hashing.
+  fn positions(&self, key: &str) -> impl Iterator<Item = usize> {
+    let len = self.bits.len() as u64 * 64;
+    let h1 = fnv1a(key.as_bytes(), 0xcbf2_9ce4_8422_2325);
+    let h2 = fnv1a(key.as_bytes(), 0x6c62_272e_07bb_0142) | 1;
+    (0..self.hashes as u64).map(move |i| (h1.wrapping_add(i.wrapping_mul(h2)) % len) as usize)
+  }
+
+  fn contains(&self, key: &str) -> bool {
+    self
+      .positions(key)
+      .all(|bit| self.bits[bit / 64] & (1 << (bit % 64)) != 0)
+  }
+
+  /// Returns whether `key` was new.
+  fn insert(&mut self, key: &str) -> bool {
+    let mut new = false;
+    for bit in self.positions(key).collect::<Vec<_>>() {
+      let mask = 1 << (bit % 64);
+      new |= self.bits[bit / 64] & mask == 0;
+      self.bits[bit / 64] |= mask;
+    }
+    new
+  }
+}
+
+#[derive(Serialize, Deserialize)]
+enum SeenSet {
+  Exact(HashSet<String>),
+  Bloom(BloomFilter),
+}
+
+impl SeenSet {
+  fn contains(&self, key: &str) -> bool {
+    match self {
+      Self::Exact(seen) => seen.contains(key),
+      Self::Bloom(bloom) => bloom.contains(key),
+    }
+  }
+
+  fn insert(&mut self, key: &str) -> bool {
+    match self {
+      Self::Exact(seen) => seen.insert(key.to_string()),
+      Self::Bloom(bloom) => bloom.insert(key),
+    }
+  }
+}
+
+#[derive(Serialize, Deserialize)]
+struct FrontierSnapshot {
+  seen: SeenSet,
+  seen_count: u32,
+  next_seq: u64,
+  /// Queued and in-flight entries; in-flight ones are queued again on restore.
+  entries: Vec<QueuedEntry>,
+}
+
+/// Snapshots are the little-endian `SNAPSHOT_VERSION` followed by the bincode-encoded
+/// `FrontierSnapshot`. Fixed-width integers keep the bloom filter bits at 8 bytes per word.
+fn snapshot_config() -> impl bincode::config::Config {
+  bincode::config::standard().with_fixed_int_encoding()
+}
+
+/// The mutable state behind `CrawlFrontier`.
+struct FrontierState {
+  seen: SeenSet,
+  /// URLs added to `seen`; a bloom filter false positive isn't counted.
+  seen_count: u32,
+  next_seq: u64,
+  queues: HashMap<String, BinaryHeap<QueuedEntry>>,
+  in_flight: HashMap<String, QueuedEntry>,
+  in_flight_per_host: HashMap<String, u32>,
+  max_in_flight_per_host: u32,
+}
+
+impl FrontierState {
+  fn new(options: &CrawlFrontierOptions, seen: Option<SeenSet>) -> Self {
+    let seen = seen.unwrap_or_else(|| match &options.bloom_filter {
+      Some(bloom) => SeenSet::Bloom(BloomFilter::new(
+        bloom.expected_urls,
+        bloom
+          .false_positive_rate
+          .unwrap_or(DEFAULT_FALSE_POSITIVE_RATE),
+      )),
+      None => SeenSet::Exact(HashSet::new()),
+    });
+
+    Self {
+      seen,
+      seen_count: 0,
+      next_seq: 0,
+      queues: HashMap::new(),
+      in_flight: HashMap::new(),
+      in_flight_per_host: HashMap::new(),
+      max_in_flight_per_host: options.max_in_flight_per_host.unwrap_or(1).max(1),
+    }
+  }
+
+  fn push(&mut self, entry: FrontierEntry) {
+    let seq = self.next_seq;
+    self.next_seq += 1;
+    self
+      .queues
+      .entry(entry.host.clone())
+      .or_default()
+      .push(QueuedEntry { entry, seq });
+  }
+
+  fn to_snapshot(&self) -> std::result::Result<Vec<u8>, bincode::error::EncodeError> {
+    #[derive(Serialize)]
+    struct SnapshotRef<'a> {
+      seen: &'a SeenSet,
+      seen_count: u32,
+      next_seq: u64,
+      entries: Vec<&'a QueuedEntry>,
+    }
+
+    let mut snapshot = SNAPSHOT_VERSION.to_le_bytes().to_vec();
+    let body = SnapshotRef {
+      seen: &self.seen,
+      seen_count: self.seen_count,
+      next_seq: self.next_seq,
+      entries: self
+        .queues
+        .values()
+        .flatten()
+        .chain(self.in_flight.values())
+        .collect(),
+    };
+    bincode::serde::encode_into_std_write(&body, &mut snapshot, snapshot_config())?;
+    Ok(snapshot)
+  }
+
+  fn from_snapshot(
+    options: &CrawlFrontierOptions,
+    snapshot: &[u8],
+  ) -> std::result::Result<Self, String> {
+    let Some((version, body)) = snapshot.split_first_chunk() else {
+      return Err("Invalid snapshot: missing version".to_string());
+    };
+    let version = u32::from_le_bytes(*version);
+    if version != SNAPSHOT_VERSION {
+      return Err(format!(
+        "Unsupported snapshot version {version}, expected {SNAPSHOT_VERSION}"
+      ));
+    }
+    let (snapshot, read): (FrontierSnapshot, usize) =
+      bincode::serde::decode_from_slice(body, snapshot_config())
+        .map_err(|e| format!("Invalid snapshot: {e}"))?;
+    if read != body.len() {
+      return Err("Invalid snapshot: trailing bytes".to_string());
+    }
+    if let SeenSet::Bloom(bloom) = &snapshot.seen {
+      if bloom.bits.is_empty() || !(1..=32).contains(&bloom.hashes) {
+        return Err(format!(
+          "Invalid snapshot: bloom filter with {} words and {} hashes",
+          bloom.bits.len(),
+          bloom.hashes
+        ));
+      }
+    }
+
+    let mut frontier = Self::new(options, Some(snapshot.seen));
+    frontier.seen_count = snapshot.seen_count;
+    frontier.next_seq = snapshot.next_seq;
+    for queued in snapshot.entries {
+      frontier
+        .queues
+        .entry(queued.entry.host.clone())
+        .or_default()
+        .push(queued);
+    }
+    Ok(frontier)
+  }
+
+  fn pop(&mut self, count: Option<u32>) -> Vec<FrontierEntry> {
+    let mut out = Vec::new();
+    for _ in 0..count.unwrap_or(1) {
+      let host = self
+        .queues
+        .iter()
+        .filter(|(host, _)| {
+          self.in_flight_per_host.get(*host).copied().unwrap_or(0) < self.max_in_flight_per_host
+        })
+        .filter_map(|(host, queue)| Some((host, queue.peek()?)))
+        .max_by(|a, b| a.1.cmp(b.1))
+        .map(|(host, _)| host.clone());
+      let Some(host) = host else {
+        break;
+      };
+
+      let queue = self.queues.get_mut(&host).expect("host was just found");
+      let queued = queue.pop().expect("queue was just peeked");
+      if queue.is_empty() {
+        self.queues.remove(&host);
+      }
+
+      *self.in_flight_per_host.entry(host).or_insert(0) += 1;
+      out.push(queued.entry.clone());
+      self.in_flight.insert(queued.entry.url.clone(), queued);
+    }
+    out
+  }
+
+  fn complete(&mut self, url: &str) -> bool {
+    let Some(queued) = self.in_flight.remove(url) else {
+      return false;
+    };
+    if let Some(count) = self.in_flight_per_host.get_mut(&queued.entry.host) {
+      *count -= 1;
+      if *count == 0 {
+        self.in_flight_per_host.remove(&queued.entry.host);
+      }
+    }
+    true
+  }
+
+  fn queued_count(&self) -> u32 {
+    self.queues.values().map(|queue| queue.len() as u32).sum()
+  }
+
+  fn in_flight_count(&self) -> u32 {
+    self.in_flight.len() as u32
+  }
+}
+
+/// Filters and resolves `links` without taking the lock, then holds it only to check them
+/// against the seen set and queue them.
+fn add_links(
+  filter: &CompiledCrawlFilter,
+  state: &Mutex<FrontierState>,
+  links: Vec<String>,
+  page: Option<FilterLinksPage>,
+) -> FilterLinksResult {
+  let page = page.unwrap_or_default();
+  let depth = page.parent_depth.map_or(0, |depth| depth.saturating_add(1));
+
+  let mut result = filter.filter_links(links, None, &page);
+  let resolved: Vec<_> = std::mem::take(&mut result.links)
+    .into_iter()
+    .filter_map(|link| {
+      let (url, key) = filter.resolve(&link)?;
+      Some((link, url, key))
+    })
+    .collect();
+
+  let mut state = state.lock().unwrap_or_else(PoisonError::into_inner);
+  let mut queued = Vec::with_capacity(resolved.len());
+  for (link, url, key) in resolved {
+    if !state.seen.insert(&key) {
+      let denial = Denial::new(DUPLICATE).tested(TESTED_URL, &key);
+      result
+        .denial_reasons
+        .insert(link.clone(), DUPLICATE.to_string());
+      result
+        .denials
+        .insert(link, denial.into_link_denial(Some(key)));
+      continue;
+    }
+    state.seen_count += 1;
+
+    let score = result
+      .scores
+      .as_ref()
+      .and_then(|scores| scores.get(&link))
+      .copied()
+      .unwrap_or(0.0);
+    state.push(FrontierEntry {
+      url: url.to_string(),
+      host: url_host(&url).to_string(),
+      depth,
+      score,
+    });
+    queued.push(link);
+  }
+  drop(state);
+
+  result.scores = result.scores.map(|scores| {
+    queued
+      .iter()
+      .filter_map(|link| Some((link.clone(), *scores.get(link)?)))
+      .collect()
+  });
+  result.link_depth = Some(depth);
+  result.links = queued;
+  result
+}
+
+/// Crawl state kept natively: seen URLs, and queued URLs per host in priority order. Each page's
+/// links are filtered, deduplicated against everything seen so far and queued in one call.
+#[napi]
+pub struct CrawlFrontier {
+  filter: Arc<CompiledCrawlFilter>,
+  state: Arc<Mutex<FrontierState>>,
+}
+
+#[napi]
+impl CrawlFrontier {
+  #[napi(constructor)]
+  pub fn new(options: CrawlFrontierOptions) -> Result<Self> {
+    let state = FrontierState::new(&options, None);
+    Self::with_state(options, state)
+  }
+
+  /// Resume a crawl from `snapshot`. `options` aren't part of the snapshot and must be passed
+  /// again; the seen set keeps the kind it was created with.
+  #[napi(factory)]
+  pub fn restore(options: CrawlFrontierOptions, snapshot: Buffer) -> Result<Self> {
+    let state = FrontierState::from_snapshot(&options, &snapshot)
+      .map_err(|e| Error::new(Status::GenericFailure, format!("Crawl frontier error: {e}")))?;
+    Self::with_state(options, state)
+  }
+
+  /// Filter links found on `page`, deny those already seen as `DUPLICATE`, and queue the rest.
+  /// `links` of the result are the URLs that were queued. Without `page.parent_depth` links
+  /// are queued at depth 0, as seeds.
+  #[napi]
+  pub async fn add_links(
+    &self,
+    links: Vec<String>,
+    page: Option<FilterLinksPage>,
+  ) -> Result<FilterLinksResult> {
+    let filter = Arc::clone(&self.filter);
+    let state = Arc::clone(&self.state);
+    task::spawn_blocking(move || add_links(&filter, &state, links, page))
+      .await
+      .map_err(|e| {
+        napi::Error::new(
+          napi::Status::GenericFailure,
+          format!("add_links join error: {e}"),
+        )
+      })
+  }
+
+  /// Take up to `count` URLs (default 1), best first, skipping hosts that already have
+  /// `max_in_flight_per_host` URLs out. Call `complete` once each URL is done.
+  #[napi]
+  pub fn pop(&self, count: Option<u32>) -> Vec<FrontierEntry> {
+    self.lock().pop(count)
+  }
+
+  /// Mark a URL returned by `pop` as done, freeing its host's slot. Returns false if it wasn't
+  /// in flight.
+  #[napi]
+  pub fn complete(&self, url: String) -> bool {
+    self.lock().complete(&url)
+  }
+
+  /// Whether `url` was already seen. With a bloom filter this can be a false positive.
+  #[napi]
+  pub fn has_seen(&self, url: String) -> bool {
+    self
+      .filter
+      .resolve(&url)
+      .is_some_and(|(_, key)| self.lock().seen.contains(&key))
+  }
+
+  #[napi]
+  pub fn queued_count(&self) -> u32 {
+    self.lock().queued_count()
+  }
+
+  #[napi]
+  pub fn in_flight_count(&self) -> u32 {
+    self.lock().in_flight_count()
+  }
+
+  #[napi]
+  pub fn seen_count(&self) -> u32 {
+    self.lock().seen_count
+  }
+
+  /// Serialize the seen set and the queue, including in-flight URLs, for `restore`.
+  #[napi]
+  pub fn snapshot(&self) -> Result<Buffer> {
+    self
+      .lock()
+      .to_snapshot()
+      .map(Buffer::from)
+      .map_err(|e| Error::new(Status::GenericFailure, format!("Crawl frontier error: {e}")))
+  }
+
+  fn with_state(options: CrawlFrontierOptions, state: FrontierState) -> Result<Self> {
+    let filter = CompiledCrawlFilter::new(options.filter)
+      .map_err(|e| Error::new(Status::GenericFailure, format!("Crawl frontier error: {e}")))?;
+    Ok(Self {
+      filter: Arc::new(filter),
+      state: Arc::new(Mutex::new(state)),
+    })
+  }
+
+  /// The lock is only held while the seen set or queues are read or changed, never while
+  /// links are filtered, so these calls don't wait on a pending `add_links`.
+  fn lock(&self) -> MutexGuard<'_, FrontierState> {
+    self.state.lock().unwrap_or_else(PoisonError::into_inner)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn options(bloom_filter: Option<BloomFilterOptions>) -> CrawlFrontierOptions {
+    CrawlFrontierOptions {
+      filter: CrawlFilterOptions {
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        allowed_hosts: Some(vec!["docs.example.com".to_string()]),
+        ..Default::default()
+      },
+      bloom_filter,
+      max_in_flight_per_host: Some(2),
+    }
+  }
+
+  fn page(parent_depth: u32) -> Option<FilterLinksPage> {
+    Some(FilterLinksPage {
+      parent_depth: Some(parent_depth),
+      ..Default::default()
+    })
+  }
+
+  /// `add_links` as the async method runs it, on the current thread.
+  fn add(
+    frontier: &CrawlFrontier,
+    links: Vec<String>,
+    page: Option<FilterLinksPage>,
+  ) -> FilterLinksResult {
+    add_links(&frontier.filter, &frontier.state, links, page)
+  }
+
+  fn urls(entries: &[FrontierEntry]) -> Vec<&str> {
+    entries.iter().map(|entry| entry.url.as_str()).collect()
+  }
+
+  #[test]
+  fn test_crawl_frontier_dedupes_and_schedules() {
+    let frontier = CrawlFrontier::new(options(None)).unwrap();
+
+    let result = add(&frontier, vec!["https://example.com/".to_string()], None);
+    assert_eq!(result.links, vec!["https://example.com/"]);
+    assert_eq!(frontier.pop(None)[0].depth, 0);
+
+    let result = add(
+      &frontier,
+      vec![
+        "/a".to_string(),
+        "/b".to_string(),
+        "/".to_string(),
+        "https://docs.example.com/guide".to_string(),
+        "https://other.com/".to_string(),
+      ],
+      page(0),
+    );
+    assert_eq!(
+      result.links,
+      vec!["/a", "/b", "https://docs.example.com/guide"]
+    );
+    assert_eq!(result.denial_reasons["/"], "DUPLICATE");
+    assert_eq!(result.denial_reasons["https://other.com/"], "EXTERNAL_LINK");
+    assert_eq!(result.link_depth, Some(1));
+    assert!(frontier.has_seen("https://example.com/a".to_string()));
+
+    // Links seen on an earlier page are duplicates, even when deeper.
+    let result = add(&frontier, vec!["/a".to_string(), "/c".to_string()], page(1));
+    assert_eq!(result.links, vec!["/c"]);
+    assert_eq!(frontier.seen_count(), 5);
+    assert_eq!(frontier.queued_count(), 4);
+
+    // example.com already has the start page in flight, so it only gets one more slot.
+    let entries = frontier.pop(Some(10));
+    assert_eq!(
+      urls(&entries),
+      vec!["https://example.com/a", "https://docs.example.com/guide",]
+    );
+    assert_eq!(frontier.in_flight_count(), 3);
+
+    assert!(frontier.complete("https://example.com/".to_string()));
+    assert!(!frontier.complete("https://example.com/".to_string()));
+    let entries = frontier.pop(Some(10));
+    assert_eq!(urls(&entries), vec!["https://example.com/b"]);
+    assert_eq!(entries[0].depth, 1);
+  }
+
+  #[test]
+  fn test_crawl_frontier_orders_by_score() {
+    let mut options = options(None);
+    options.filter.scoring = Some(crate::scoring::LinkScoringOptions {
+      prompt: Some("pricing".to_string()),
+      ..Default::default()
+    });
+    let frontier = CrawlFrontier::new(options).unwrap();
+
+    add(
+      &frontier,
+      vec!["/blog".to_string(), "/pricing".to_string()],
+      page(0),
+    );
+    let entries = frontier.pop(Some(2));
+    assert_eq!(
+      urls(&entries),
+      vec!["https://example.com/pricing", "https://example.com/blog"]
+    );
+    assert!(entries[0].score > entries[1].score);
+  }
+
+  #[test]
+  fn test_crawl_frontier_snapshot_round_trip() {
+    for bloom_filter in [
+      None,
+      Some(BloomFilterOptions {
+        expected_urls: 1000,
+        false_positive_rate: None,
+      }),
+    ] {
+      let bloom = bloom_filter.is_some();
+      let frontier = CrawlFrontier::new(options(bloom_filter)).unwrap();
+      add(
+        &frontier,
+        vec!["/a".to_string(), "/b".to_string(), "/c".to_string()],
+        page(0),
+      );
+      assert_eq!(urls(&frontier.pop(None)), vec!["https://example.com/a"]);
+
+      let snapshot = frontier.lock().to_snapshot().unwrap();
+      if let SeenSet::Bloom(bloom) = &frontier.lock().seen {
+        // Bloom filter words are stored as raw bytes.
+        assert!(snapshot.len() < bloom.bits.len() * 8 + 256);
+      }
+      let restored = CrawlFrontier::restore(options(None), Buffer::from(snapshot)).unwrap();
+      assert_eq!(matches!(restored.lock().seen, SeenSet::Bloom(_)), bloom);
+      assert_eq!(restored.seen_count(), 3);
+      // The in-flight URL is queued again, ahead of later ones.
+      assert_eq!(restored.queued_count(), 3);
+      assert_eq!(restored.in_flight_count(), 0);
+
+      let result = add(&restored, vec!["/b".to_string(), "/d".to_string()], page(0));
+      assert_eq!(result.links, vec!["/d"]);
+      assert_eq!(
+        urls(&restored.pop(Some(2))),
+        vec!["https://example.com/a", "https://example.com/b"]
+      );
+    }
+
+    let restore = |snapshot: &[u8]| {
+      FrontierState::from_snapshot(&options(None), snapshot)
+        .err()
+        .unwrap()
+    };
+    assert!(restore(b"{}").starts_with("Invalid snapshot"));
+
+    let mut snapshot = FrontierState::new(&options(None), None)
+      .to_snapshot()
+      .unwrap();
+    snapshot[0] = 2;
+    assert_eq!(
+      restore(&snapshot),
+      "Unsupported snapshot version 2, expected 1"
+    );
+
+    // A bloom filter without bits or hashes would panic or see every URL.
+    for (bits, hashes) in [(vec![], 7), (vec![0; 4], 0), (vec![0; 4], 33)] {
+      let seen = SeenSet::Bloom(BloomFilter { bits, hashes });
+      let snapshot = FrontierState::new(&options(None), Some(seen))
+        .to_snapshot()
+        .unwrap();
+      assert!(restore(&snapshot).starts_with("Invalid snapshot: bloom filter"));
+    }
+  }
+
+  #[test]
+  fn test_bloom_filter() {
+    let mut bloom = BloomFilter::new(1000, 0.01);
+    assert_eq!(bloom.hashes, 7);
+    assert!(bloom.insert("https://example.com/a"));
+    assert!(!bloom.insert("https://example.com/a"));
+    assert!(bloom.contains("https://example.com/a"));
+
+    for i in 0..1000 {
+      bloom.insert(&format!("https://example.com/{i}"));
+    }
+    let false_positives = (0..1000)
+      .filter(|i| bloom.contains(&format!("https://example.org/{i}")))
+      .count();
+    assert!(false_positives < 30, "{false_positives} false positives");
+  }
+}
@@ -14,6 +14,8 @@ use url::Url;
 static URL_REGEX: LazyLock<Regex> =
   LazyLock::new(|| Regex::new(r#"url\(['"]?([^'")]+)['"]?\)"#).expect("URL_REGEX is a valid static regex pattern"));
 
+use crate::crawler::{robots_directives, LinkHint, PageRobotsDirectives, SitemapAlternate};
+use crate::pagination::{_detect_pagination, DetectPaginationCall, PaginationResult};
 use crate::utils::to_napi_err;
 
 fn _extract_base_href_from_document(
@@ -77,18 +79,12 @@ pub async fn extract_links(html: Option<String>) -> napi::Result<Vec<String>> {
     let mut out: Vec<String> = Vec::new();
 
     for anchor in anchors {
-      let mut href = match anchor.attributes.borrow().get("href") {
+      let href = match anchor.attributes.borrow().get("href") {
         Some(x) => x.to_string(),
         None => continue,
       };
 
-      if href.starts_with("http:/") && !href.starts_with("http://") {
-        href = format!("http://{}", &href[6..]);
-      } else if href.starts_with("https:/") && !href.starts_with("https://") {
-        href = format!("https://{}", &href[7..]);
-      }
-
-      out.push(href);
+      out.push(fix_scheme_slashes(href));
     }
 
     Ok(out)
@@ -102,6 +98,345 @@ pub async fn extract_links(html: Option<String>) -> napi::Result<Vec<String>> {
   })?
 }
 
+/// Repairs `http:/host` and `https:/host`, which browsers accept but `Url::join` reads as paths.
+fn fix_scheme_slashes(href: String) -> String {
+  if href.starts_with("http:/") && !href.starts_with("http://") {
+    format!("http://{}", &href[6..])
+  } else if href.starts_with("https:/") && !href.starts_with("https://") {
+    format!("https://{}", &href[7..])
+  } else {
+    href
+  }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+  text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// A link together with what its `<a>` element says about it.
+#[derive(Serialize)]
+#[napi(object)]
+pub struct DetailedLink {
+  /// The `href` attribute, with `http:/host` typos repaired.
+  pub href: String,
+  /// `href` resolved against `<base href>` and the page URL, if it resolves.
+  pub url: Option<String>,
+  /// Visible text with whitespace collapsed, falling back to `aria-label` and then image `alt`.
+  pub text: String,
+  /// Lowercased `rel` tokens, e.g. `nofollow`, `ugc` or `sponsored`.
+  pub rel: Vec<String>,
+  pub title: Option<String>,
+  pub hreflang: Option<String>,
+  pub target: Option<String>,
+  pub aria_label: Option<String>,
+  /// Nearest enclosing `nav`, `header`, `footer`, `main` or `aside`, by tag or ARIA role.
+  pub landmark: Option<String>,
+}
+
+fn enclosing_landmark(node: &NodeRef) -> Option<&'static str> {
+  node.ancestors().find_map(|ancestor| {
+    let element = ancestor.as_element()?;
+    let role = element
+      .attributes
+      .borrow()
+      .get("role")
+      .map(|role| role.trim().to_ascii_lowercase());
+    let landmark = match role.as_deref() {
+      Some("navigation") => "nav",
+      Some("banner") => "header",
+      Some("contentinfo") => "footer",
+      Some("main") => "main",
+      Some("complementary") => "aside",
+      _ => match &*element.name.local {
+        "nav" => "nav",
+        "header" => "header",
+        "footer" => "footer",
+        "main" => "main",
+        "aside" => "aside",
+        _ => return None,
+      },
+    };
+    Some(landmark)
+  })
+}
+
+fn _extract_links_detailed(
+  html: &str,
+  url: &str,
+) -> Result<Vec<DetailedLink>, Box<dyn std::error::Error + Send + Sync>> {
+  let document = parse_html().one(html);
+  let base_url = Url::parse(&_extract_base_href_from_document(
+    &document,
+    &Url::parse(url)?,
+  )?)?;
+
+  let anchors: Vec<_> = document
+    .select("a[href]")
+    .map_err(|_| "Failed to select links")?
+    .collect();
+
+  let mut out = Vec::new();
+
+  for anchor in anchors {
+    let attributes = anchor.attributes.borrow();
+    let href = match attributes.get("href") {
+      Some(x) => fix_scheme_slashes(x.to_string()),
+      None => continue,
+    };
+    let attribute = |name: &str| {
+      attributes
+        .get(name)
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+    };
+
+    let mut text = collapse_whitespace(&anchor.as_node().text_contents());
+    if text.is_empty() {
+      text = attribute("aria-label")
+        .or_else(|| {
+          anchor
+            .as_node()
+            .select("img[alt]")
+            .ok()?
+            .next()
+            .and_then(|img| img.attributes.borrow().get("alt").map(collapse_whitespace))
+        })
+        .unwrap_or_default();
+    }
+
+    out.push(DetailedLink {
+      url: base_url.join(&href).ok().map(String::from),
+      href,
+      text,
+      rel: attributes
+        .get("rel")
+        .map(|rel| {
+          rel
+            .split_whitespace()
+            .map(str::to_ascii_lowercase)
+            .collect()
+        })
+        .unwrap_or_default(),
+      title: attribute("title"),
+      hreflang: attribute("hreflang"),
+      target: attribute("target"),
+      aria_label: attribute("aria-label"),
+      landmark: enclosing_landmark(anchor.as_node()).map(str::to_string),
+    });
+  }
+
+  Ok(out)
+}
+
+/// Extract links from HTML document with their anchor text, `rel`, landmark and other context.
+#[napi]
+pub async fn extract_links_detailed(
+  html: Option<String>,
+  url: String,
+) -> napi::Result<Vec<DetailedLink>> {
+  let res = task::spawn_blocking(move || match html {
+    Some(html) => _extract_links_detailed(&html, &url),
+    None => Ok(Vec::new()),
+  })
+  .await
+  .map_err(|e| {
+    napi::Error::new(
+      napi::Status::GenericFailure,
+      format!("extract_links_detailed join error: {e}"),
+    )
+  })?;
+
+  res.map_err(to_napi_err)
+}
+
+fn _extract_locale_alternates(
+  html: &str,
+  url: &str,
+) -> Result<Vec<SitemapAlternate>, Box<dyn std::error::Error + Send + Sync>> {
+  let document = parse_html().one(html);
+  let base_url = Url::parse(&_extract_base_href_from_document(
+    &document,
+    &Url::parse(url)?,
+  )?)?;
+
+  let alternates = document
+    .select("link[rel][hreflang][href]")
+    .map_err(|_| "Failed to select alternate links")?
+    .filter_map(|link| {
+      let attributes = link.attributes.borrow();
+      let is_alternate = attributes
+        .get("rel")?
+        .split_whitespace()
+        .any(|rel| rel.eq_ignore_ascii_case("alternate"));
+      if !is_alternate {
+        return None;
+      }
+      Some(SitemapAlternate {
+        hreflang: attributes.get("hreflang")?.trim().to_string(),
+        href: base_url
+          .join(attributes.get("href")?.trim())
+          .ok()?
+          .to_string(),
+      })
+    })
+    .collect();
+
+  Ok(alternates)
+}
+
+/// Extract the page's `<link rel="alternate" hreflang>` tags, with `href` resolved, for the
+/// link filter's `locale_alternates`.
+#[napi]
+pub async fn extract_locale_alternates(
+  html: Option<String>,
+  url: String,
+) -> napi::Result<Vec<SitemapAlternate>> {
+  let res = task::spawn_blocking(move || match html {
+    Some(html) => _extract_locale_alternates(&html, &url),
+    None => Ok(Vec::new()),
+  })
+  .await
+  .map_err(|e| {
+    napi::Error::new(
+      napi::Status::GenericFailure,
+      format!("extract_locale_alternates join error: {e}"),
+    )
+  })?;
+
+  res.map_err(to_napi_err)
+}
+
+fn _extract_pagination(
+  html: &str,
+  url: &str,
+) -> Result<PaginationResult, Box<dyn std::error::Error + Send + Sync>> {
+  let document = parse_html().one(html);
+  // Resolved against `<base href>`, like the anchors from `_extract_links_detailed`.
+  let base_url = Url::parse(&_extract_base_href_from_document(
+    &document,
+    &Url::parse(url)?,
+  )?)?;
+  let resolve = |href: &str| base_url.join(href.trim()).ok().map(String::from);
+
+  let mut rel_next = None;
+  let mut rel_prev = None;
+  for link in document
+    .select("link[rel][href]")
+    .map_err(|_| "Failed to select rel links")?
+  {
+    let attributes = link.attributes.borrow();
+    let (Some(rel), Some(href)) = (attributes.get("rel"), attributes.get("href")) else {
+      continue;
+    };
+    for rel in rel.split_whitespace() {
+      let slot = if rel.eq_ignore_ascii_case("next") {
+        &mut rel_next
+      } else if rel.eq_ignore_ascii_case("prev") || rel.eq_ignore_ascii_case("previous") {
+        &mut rel_prev
+      } else {
+        continue;
+      };
+      if slot.is_none() {
+        *slot = resolve(href);
+      }
+    }
+  }
+
+  let mut links = Vec::new();
+  let mut link_hints = HashMap::new();
+  for link in _extract_links_detailed(html, url)? {
+    let Some(link_url) = link.url else {
+      continue;
+    };
+    link_hints
+      .entry(link_url.clone())
+      .or_insert_with(|| LinkHint {
+        anchor_text: Some(link.text),
+        rel: Some(link.rel),
+        aria_label: link.aria_label,
+        ..Default::default()
+      });
+    links.push(link_url);
+  }
+
+  Ok(_detect_pagination(DetectPaginationCall {
+    url: url.to_string(),
+    links,
+    link_hints: Some(link_hints),
+    rel_next,
+    rel_prev,
+  })?)
+}
+
+/// Detect the "next page" link and pagination chain of a listing page, and split its links
+/// into pagination and content links.
+#[napi]
+pub async fn extract_pagination(
+  html: Option<String>,
+  url: String,
+) -> napi::Result<PaginationResult> {
+  let res =
+    task::spawn_blocking(move || _extract_pagination(html.as_deref().unwrap_or_default(), &url))
+      .await
+      .map_err(|e| {
+        napi::Error::new(
+          napi::Status::GenericFailure,
+          format!("extract_pagination join error: {e}"),
+        )
+      })?;
+
+  res.map_err(to_napi_err)
+}
+
+fn _extract_robots_directives(
+  html: &str,
+  x_robots_tags: &[String],
+  user_agents: Option<&[String]>,
+) -> Result<PageRobotsDirectives, Box<dyn std::error::Error + Send + Sync>> {
+  let document = parse_html().one(html);
+
+  let meta: Vec<(String, String)> = document
+    .select("meta[name][content]")
+    .map_err(|_| "Failed to select meta tags")?
+    .filter_map(|meta| {
+      let attributes = meta.attributes.borrow();
+      Some((
+        attributes.get("name")?.to_string(),
+        attributes.get("content")?.to_string(),
+      ))
+    })
+    .collect();
+
+  Ok(robots_directives(&meta, x_robots_tags, user_agents))
+}
+
+/// Extract the page's `noindex`, `nofollow` and `noarchive` directives from robots meta tags
+/// and `X-Robots-Tag` header values.
+#[napi]
+pub async fn extract_robots_directives(
+  html: Option<String>,
+  x_robots_tags: Option<Vec<String>>,
+  user_agents: Option<Vec<String>>,
+) -> napi::Result<PageRobotsDirectives> {
+  let res = task::spawn_blocking(move || {
+    _extract_robots_directives(
+      html.as_deref().unwrap_or_default(),
+      x_robots_tags.as_deref().unwrap_or_default(),
+      user_agents.as_deref(),
+    )
+  })
+  .await
+  .map_err(|e| {
+    napi::Error::new(
+      napi::Status::GenericFailure,
+      format!("extract_robots_directives join error: {e}"),
+    )
+  })?;
+
+  res.map_err(to_napi_err)
+}
+
 macro_rules! insert_meta_name {
   ($out:ident, $document:ident, $metaName:expr, $outName:expr) => {
     if let Some(x) = $document
@@ -1012,3 +1347,120 @@ fn remove_skip_to_content_links(input: &str) -> String {
 
   out
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_extract_links_detailed() {
+    let html = r#"
+      <html>
+        <head><base href="/docs/"></head>
+        <body>
+          <header><a href="/">Home</a></header>
+          <div role="navigation">
+            <a href="guide" rel="NoFollow  UGC" title=" Guide " hreflang="en" target="_blank">
+              The
+              guide
+            </a>
+          </div>
+          <main>
+            <a href="next" aria-label="Next page"></a>
+            <a href="logo"><img src="logo.png" alt=" Acme   logo "></a>
+            <aside><a href="https://other.com/x" title="">   </a></aside>
+          </main>
+          <div role="contentinfo"><nav><a href="//cdn.example.com/a">CDN</a></nav></div>
+          <a href="http://[::1">Broken</a>
+        </body>
+      </html>
+    "#;
+
+    let links = _extract_links_detailed(html, "https://example.com/blog/post").unwrap();
+    let summary: Vec<_> = links
+      .iter()
+      .map(|link| {
+        (
+          link.url.as_deref(),
+          link.text.as_str(),
+          link.landmark.as_deref(),
+        )
+      })
+      .collect();
+    assert_eq!(
+      summary,
+      vec![
+        (Some("https://example.com/"), "Home", Some("header")),
+        (
+          Some("https://example.com/docs/guide"),
+          "The guide",
+          Some("nav")
+        ),
+        (
+          Some("https://example.com/docs/next"),
+          "Next page",
+          Some("main")
+        ),
+        (
+          Some("https://example.com/docs/logo"),
+          "Acme logo",
+          Some("main")
+        ),
+        (Some("https://other.com/x"), "", Some("aside")),
+        (Some("https://cdn.example.com/a"), "CDN", Some("nav")),
+        (None, "Broken", None),
+      ]
+    );
+
+    let guide = &links[1];
+    assert_eq!(guide.href, "guide");
+    assert_eq!(guide.rel, vec!["nofollow", "ugc"]);
+    assert_eq!(guide.title.as_deref(), Some("Guide"));
+    assert_eq!(guide.hreflang.as_deref(), Some("en"));
+    assert_eq!(guide.target.as_deref(), Some("_blank"));
+    assert_eq!(links[2].aria_label.as_deref(), Some("Next page"));
+    assert!(links[4].rel.is_empty());
+    assert_eq!(links[4].title, None);
+  }
+
+  #[test]
+  fn test_extract_pagination_resolves_rel_links_against_base_href() {
+    let html = r#"
+      <html>
+        <head>
+          <base href="https://example.com/blog/">
+          <link rel="next" href="page/3">
+        </head>
+        <body>
+          <a href="page/1">Previous</a>
+          <a href="page/3">3</a>
+          <a href="hello-world">Hello world</a>
+        </body>
+      </html>
+    "#;
+
+    let result = _extract_pagination(html, "https://example.com/blog/page/2").unwrap();
+    assert_eq!(result.page, 2);
+    assert_eq!(
+      result.next.as_deref(),
+      Some("https://example.com/blog/page/3")
+    );
+    assert_eq!(result.next_signal.as_deref(), Some("rel"));
+    assert_eq!(
+      result.prev.as_deref(),
+      Some("https://example.com/blog/page/1")
+    );
+    assert_eq!(
+      result
+        .chain
+        .iter()
+        .map(|page| page.page)
+        .collect::<Vec<_>>(),
+      vec![1, 2, 3]
+    );
+    assert_eq!(
+      result.content_links,
+      vec!["https://example.com/blog/hello-world"]
+    );
+  }
+}
@@ -1,18 +1,34 @@
 #![deny(clippy::all)]
 
+pub use crate::classifier::*;
 pub use crate::crawler::*;
 pub use crate::engpicker::*;
+pub use crate::frontier::*;
 pub use crate::html::*;
+pub use crate::normalizer::*;
+pub use crate::pagination::*;
 pub use crate::pdf::*;
+pub use crate::robots::*;
+pub use crate::scheduler::*;
+pub use crate::scoring::*;
+pub use crate::url_space::*;
 pub use crate::utils::*;
 
 pub use crate::document::{DocumentConverter, DocumentType};
 
+mod classifier;
 mod crawler;
 mod document;
 mod engpicker;
+mod frontier;
 mod html;
+mod normalizer;
+mod pagination;
 mod pdf;
+mod robots;
+mod scheduler;
+mod scoring;
+mod url_space;
 mod utils;
 
 pub use napi::bindgen_prelude::*;
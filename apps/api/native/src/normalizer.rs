@@ -0,0 +1,358 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use tokio::task;
+use url::{form_urlencoded, Url};
+
+use crate::crawler::{no_sections, FragmentPolicy};
+
+/// Rules for reducing URLs to a canonical form. Scheme and host case, default ports and dot
+/// segments are always normalized, and fragments are dropped unless they look like routes.
+#[derive(Deserialize, Default, Clone)]
+#[napi(object)]
+pub struct UrlNormalizationOptions {
+  /// Drop the whole query string. Defaults to false.
+  pub strip_query: Option<bool>,
+  /// Sort query parameters by name. Defaults to true.
+  pub sort_query_params: Option<bool>,
+  /// Drop `utm_*` and well-known click-id parameters. Defaults to true.
+  pub strip_tracking_params: Option<bool>,
+  /// Additional parameter names to drop, matched case-insensitively.
+  pub tracking_params: Option<Vec<String>>,
+  /// Collapse `index.html`, `index.php` and similar to their directory. Defaults to true.
+  pub collapse_index_files: Option<bool>,
+  /// Drop the trailing slash from non-root paths. Defaults to true.
+  pub strip_trailing_slash: Option<bool>,
+}
+
+#[derive(Deserialize, Default)]
+#[napi(object)]
+pub struct DedupeLinksCall {
+  pub links: Vec<String>,
+  /// Base for resolving relative links. Links that don't resolve are kept as-is.
+  pub base_url: Option<String>,
+  pub normalization: Option<UrlNormalizationOptions>,
+}
+
+#[derive(Serialize)]
+#[napi(object)]
+pub struct DedupeLinksResult {
+  /// First occurrence of each canonical URL, as given.
+  pub links: Vec<String>,
+  /// Dropped links mapped to the kept link they duplicate.
+  pub duplicates: HashMap<String, String>,
+}
+
+static TRACKING_PARAMS: &[&str] = &[
+  "fbclid", "gclid", "gbraid", "wbraid", "dclid", "msclkid", "yclid", "twclid", "igshid", "mc_cid",
+  "mc_eid", "_ga", "_gl", "_hsenc", "_hsmi", "mkt_tok", "ref_src",
+];
+
+static INDEX_FILES: &[&str] = &[
+  "index.html",
+  "index.htm",
+  "index.php",
+  "index.asp",
+  "index.aspx",
+  "default.asp",
+  "default.aspx",
+];
+
+/// `UrlNormalizationOptions` with defaults applied.
+pub(crate) struct UrlNormalizer {
+  pub(crate) strip_query: bool,
+  pub(crate) sort_query_params: bool,
+  pub(crate) strip_tracking_params: bool,
+  pub(crate) tracking_params: HashSet<String>,
+  pub(crate) collapse_index_files: bool,
+  pub(crate) strip_trailing_slash: bool,
+  /// Replaces the default route heuristic for fragments when set.
+  pub(crate) fragment_policy: Option<FragmentPolicy>,
+}
+
+impl UrlNormalizer {
+  pub(crate) fn new(options: &UrlNormalizationOptions) -> Self {
+    Self {
+      strip_query: options.strip_query.unwrap_or(false),
+      sort_query_params: options.sort_query_params.unwrap_or(true),
+      strip_tracking_params: options.strip_tracking_params.unwrap_or(true),
+      tracking_params: options
+        .tracking_params
+        .iter()
+        .flatten()
+        .map(|param| param.to_lowercase())
+        .collect(),
+      collapse_index_files: options.collapse_index_files.unwrap_or(true),
+      strip_trailing_slash: options.strip_trailing_slash.unwrap_or(true),
+      fragment_policy: None,
+    }
+  }
+
+  fn is_tracking_param(&self, name: &str) -> bool {
+    let name = name.to_lowercase();
+    if self.tracking_params.contains(&name) {
+      return true;
+    }
+    self.strip_tracking_params
+      && (name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str()))
+  }
+
+  pub(crate) fn normalize(&self, mut url: Url) -> String {
+    if url.cannot_be_a_base() {
+      return url.into();
+    }
+
+    match &self.fragment_policy {
+      Some(policy) => policy.apply(&mut url),
+      None if url.fragment().is_some() && !no_sections(url.as_str()) => url.set_fragment(None),
+      None => {}
+    }
+
+    if self.strip_query {
+      url.set_query(None);
+    } else if let Some(query) = url.query() {
+      // Parameters are kept as written: re-serializing would turn `?foo` into `?foo=` and
+      // change escaping, which some servers treat as a different resource.
+      let mut params: Vec<&str> = query
+        .split('&')
+        .filter(|param| {
+          !form_urlencoded::parse(param.as_bytes())
+            .next()
+            .is_some_and(|(name, _)| self.is_tracking_param(&name))
+        })
+        .collect();
+      if self.sort_query_params {
+        params.sort_by(|a, b| a.split('=').next().cmp(&b.split('=').next()));
+      }
+      let query = params.join("&");
+      url.set_query((!query.is_empty()).then_some(query.as_str()));
+    }
+
+    let mut path = url.path().to_string();
+    if self.collapse_index_files {
+      if let Some(slash) = path.rfind('/') {
+        if INDEX_FILES.contains(&path[slash + 1..].to_lowercase().as_str()) {
+          path.truncate(slash + 1);
+        }
+      }
+    }
+    if self.strip_trailing_slash {
+      let trimmed = path.trim_end_matches('/');
+      path = if trimmed.is_empty() {
+        "/".to_string()
+      } else {
+        trimmed.to_string()
+      };
+    }
+    url.set_path(&path);
+
+    url.into()
+  }
+}
+
+fn _normalize_url(
+  url: String,
+  options: Option<UrlNormalizationOptions>,
+) -> std::result::Result<String, String> {
+  let url = Url::parse(&url).map_err(|e| format!("URL parse error: {e}"))?;
+  Ok(UrlNormalizer::new(&options.unwrap_or_default()).normalize(url))
+}
+
+/// Reduce a URL to its canonical form for deduplication.
+#[napi]
+pub async fn normalize_url(
+  url: String,
+  options: Option<UrlNormalizationOptions>,
+) -> Result<String> {
+  let res = task::spawn_blocking(move || _normalize_url(url, options))
+    .await
+    .map_err(|e| {
+      napi::Error::new(
+        napi::Status::GenericFailure,
+        format!("normalize_url join error: {e}"),
+      )
+    })?;
+
+  res.map_err(|e| Error::new(Status::GenericFailure, format!("Normalize URL error: {e}")))
+}
+
+fn _dedupe_links(data: DedupeLinksCall) -> std::result::Result<DedupeLinksResult, String> {
+  let base_url = data
+    .base_url
+    .as_deref()
+    .map(Url::parse)
+    .transpose()
+    .map_err(|e| format!("Base URL parse error: {e}"))?;
+  let normalizer = UrlNormalizer::new(&data.normalization.unwrap_or_default());
+
+  let mut kept: HashMap<String, usize> = HashMap::new();
+  let mut links: Vec<String> = Vec::new();
+  let mut duplicates = HashMap::new();
+
+  for link in data.links {
+    let resolved = match &base_url {
+      Some(base_url) => base_url.join(&link),
+      None => Url::parse(&link),
+    };
+    let canonical = match resolved {
+      Ok(url) => normalizer.normalize(url),
+      Err(_) => link.clone(),
+    };
+
+    match kept.get(&canonical) {
+      Some(&index) => {
+        duplicates.insert(link, links[index].clone());
+      }
+      None => {
+        kept.insert(canonical, links.len());
+        links.push(link);
+      }
+    }
+  }
+
+  Ok(DedupeLinksResult { links, duplicates })
+}
+
+/// Drop links that normalize to the same canonical URL, keeping the first occurrence.
+#[napi]
+pub async fn dedupe_links(data: DedupeLinksCall) -> Result<DedupeLinksResult> {
+  let res = task::spawn_blocking(move || _dedupe_links(data))
+    .await
+    .map_err(|e| {
+      napi::Error::new(
+        napi::Status::GenericFailure,
+        format!("dedupe_links join error: {e}"),
+      )
+    })?;
+
+  res.map_err(|e| Error::new(Status::GenericFailure, format!("Dedupe links error: {e}")))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::crawler::{_filter_links, CrawlFilterOptions, FilterLinksCall};
+
+  #[test]
+  fn test_normalize_url() {
+    let normalize = |url: &str| _normalize_url(url.to_string(), None).unwrap();
+
+    assert_eq!(
+      normalize("HTTPS://Example.COM:443/blog/?utm_source=x&b=2&a=1&fbclid=y#top"),
+      "https://example.com/blog?a=1&b=2"
+    );
+    assert_eq!(
+      normalize("https://example.com/docs/index.html"),
+      "https://example.com/docs"
+    );
+    assert_eq!(
+      normalize("https://example.com/index.php"),
+      "https://example.com/"
+    );
+    assert_eq!(
+      normalize("https://example.com/app#/settings"),
+      "https://example.com/app#/settings"
+    );
+    assert_eq!(
+      normalize("https://example.com/?utm_medium=email"),
+      "https://example.com/"
+    );
+
+    let options = UrlNormalizationOptions {
+      strip_query: Some(true),
+      strip_trailing_slash: Some(false),
+      collapse_index_files: Some(false),
+      ..Default::default()
+    };
+    assert_eq!(
+      _normalize_url(
+        "https://example.com/a/index.html?page=2".to_string(),
+        Some(options)
+      )
+      .unwrap(),
+      "https://example.com/a/index.html"
+    );
+
+    let options = UrlNormalizationOptions {
+      sort_query_params: Some(false),
+      strip_tracking_params: Some(false),
+      tracking_params: Some(vec!["SessionId".to_string()]),
+      ..Default::default()
+    };
+    assert_eq!(
+      _normalize_url(
+        "https://example.com/?b=1&utm_source=x&sessionid=abc&a=2".to_string(),
+        Some(options)
+      )
+      .unwrap(),
+      "https://example.com/?b=1&utm_source=x&a=2"
+    );
+
+    // Remaining parameters keep their exact spelling.
+    assert_eq!(
+      normalize("https://example.com/search?q=a+b%2Fc&utm_source=x&flag&e=%7E"),
+      "https://example.com/search?e=%7E&flag&q=a+b%2Fc"
+    );
+
+    assert!(_normalize_url("/relative".to_string(), None).is_err());
+  }
+
+  #[test]
+  fn test_dedupe_links_and_duplicate_denials() {
+    let links = vec![
+      "https://example.com/blog/".to_string(),
+      "https://example.com/blog".to_string(),
+      "/blog/index.html".to_string(),
+      "https://EXAMPLE.com:443/blog?utm_campaign=spring".to_string(),
+      "https://example.com/blog?page=2".to_string(),
+    ];
+
+    let result = _dedupe_links(DedupeLinksCall {
+      links: links.clone(),
+      base_url: Some("https://example.com".to_string()),
+      normalization: None,
+    })
+    .unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://example.com/blog/",
+        "https://example.com/blog?page=2"
+      ]
+    );
+    assert_eq!(result.duplicates.len(), 3);
+    assert_eq!(
+      result.duplicates["/blog/index.html"],
+      "https://example.com/blog/"
+    );
+
+    let call = |normalization| FilterLinksCall {
+      links: links.clone(),
+      options: CrawlFilterOptions {
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        normalization,
+        ..Default::default()
+      },
+      ..Default::default()
+    };
+
+    let result = _filter_links(call(None)).unwrap();
+    assert_eq!(result.links.len(), 5);
+
+    let result = _filter_links(call(Some(UrlNormalizationOptions::default()))).unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://example.com/blog/",
+        "https://example.com/blog?page=2"
+      ]
+    );
+    assert_eq!(
+      result.denial_reasons["https://example.com/blog"],
+      "DUPLICATE"
+    );
+  }
+}
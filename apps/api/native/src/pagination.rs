@@ -0,0 +1,425 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::{BTreeMap, HashMap},
+  sync::LazyLock,
+};
+use tokio::task;
+use url::Url;
+
+use crate::crawler::LinkHint;
+
+#[derive(Deserialize, Default)]
+#[napi(object)]
+pub struct DetectPaginationCall {
+  /// URL of the page the links were found on.
+  pub url: String,
+  pub links: Vec<String>,
+  /// `rel`, anchor text and `aria_label` hints keyed by link.
+  pub link_hints: Option<HashMap<String, LinkHint>>,
+  /// `href` of the page's `<link rel="next">`.
+  pub rel_next: Option<String>,
+  /// `href` of the page's `<link rel="prev">`.
+  pub rel_prev: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[napi(object)]
+pub struct PaginationPage {
+  pub url: String,
+  pub page: u32,
+}
+
+#[derive(Serialize, Debug)]
+#[napi(object)]
+pub struct PaginationResult {
+  /// Number of the current page; 1 when the URL doesn't carry one.
+  pub page: u32,
+  pub next: Option<String>,
+  /// How `next` was found: `rel`, `label` or `pattern` (a numbered link to the following page).
+  pub next_signal: Option<String>,
+  pub prev: Option<String>,
+  /// Pages of the series known from this page, including it, by page number.
+  pub chain: Vec<PaginationPage>,
+  /// Links that move through the series, in the order given.
+  pub pagination_links: Vec<String>,
+  /// All other links, in the order given.
+  pub content_links: Vec<String>,
+}
+
+const PAGINATION_SIGNAL_REL: &str = "rel";
+const PAGINATION_SIGNAL_LABEL: &str = "label";
+const PAGINATION_SIGNAL_PATTERN: &str = "pattern";
+
+/// Query parameters holding a page number. `p` is left out: WordPress uses it for post IDs.
+static PAGE_PARAMS: &[&str] = &[
+  "page",
+  "pg",
+  "paged",
+  "pagenum",
+  "pageno",
+  "page_no",
+  "page_number",
+  "pagina",
+  "seite",
+];
+
+static PAGE_PATH: LazyLock<Regex> = LazyLock::new(|| {
+  Regex::new(r"(?i)/(?:page|seite|pagina)[/_-]?([0-9]{1,6})/?$")
+    .expect("PAGE_PATH is a valid static regex pattern")
+});
+static NEXT_LABEL: LazyLock<Regex> = LazyLock::new(|| {
+  Regex::new(
+    r"(?i)^(?:next(?: page)?|older(?: posts| entries)?|weiter|nächste(?: seite)?|suivante?|siguiente|[›»→>]+)(?: ?[›»→>]+)?$",
+  )
+  .expect("NEXT_LABEL is a valid static regex pattern")
+});
+static PREV_LABEL: LazyLock<Regex> = LazyLock::new(|| {
+  Regex::new(
+    r"(?i)^(?:[‹«←<]+ ?)?(?:prev(?:ious)?(?: page)?|newer(?: posts| entries)?|zurück|vorherige(?: seite)?|précédente?|anterior|[‹«←<]+)$",
+  )
+  .expect("PREV_LABEL is a valid static regex pattern")
+});
+
+/// Splits a URL into the URL of its series with the page number taken out, and that number.
+fn pagination_series(url: &Url) -> (String, Option<u32>) {
+  let mut series = url.clone();
+  series.set_fragment(None);
+
+  let mut page = None;
+  if url.query().is_some() {
+    let params: Vec<(String, String)> = url
+      .query_pairs()
+      .filter(|(name, value)| {
+        if page.is_none() && PAGE_PARAMS.contains(&name.to_lowercase().as_str()) {
+          page = value.parse().ok();
+          return page.is_none();
+        }
+        true
+      })
+      .map(|(name, value)| (name.into_owned(), value.into_owned()))
+      .collect();
+    if params.is_empty() {
+      series.set_query(None);
+    } else {
+      series.query_pairs_mut().clear().extend_pairs(params);
+    }
+  }
+
+  if page.is_none() {
+    if let Some(captures) = PAGE_PATH.captures(url.path()) {
+      page = captures[1].parse().ok();
+      let start = captures.get(0).map_or(0, |m| m.start());
+      let path = url.path()[..=start].to_string();
+      series.set_path(&path);
+    }
+  }
+
+  (series.into(), page)
+}
+
+/// Finds the next and previous pages of a paginated listing among its links, and separates
+/// links that page through it from content links. `rel` beats `aria-label` or anchor text,
+/// which beats a numbered link to the following page.
+pub(crate) fn _detect_pagination(
+  data: DetectPaginationCall,
+) -> std::result::Result<PaginationResult, String> {
+  let url = Url::parse(&data.url).map_err(|e| format!("URL parse error: {e}"))?;
+  let (series, page) = pagination_series(&url);
+  let page = page.unwrap_or(1);
+  let hints = data.link_hints.unwrap_or_default();
+
+  let resolve = |link: &str| url.join(link).ok();
+  let mut next = data
+    .rel_next
+    .as_deref()
+    .and_then(resolve)
+    .map(|next| (next, PAGINATION_SIGNAL_REL));
+  let mut prev = data
+    .rel_prev
+    .as_deref()
+    .and_then(resolve)
+    .map(|prev| (prev, PAGINATION_SIGNAL_REL));
+  let mut numbered = Vec::new();
+  let mut pagination_links = Vec::new();
+  let mut content_links = Vec::new();
+
+  let rank = |signal: &str| match signal {
+    PAGINATION_SIGNAL_REL => 2,
+    PAGINATION_SIGNAL_LABEL => 1,
+    _ => 0,
+  };
+  let better = |current: &Option<(Url, &str)>, signal: &str| {
+    current
+      .as_ref()
+      .is_none_or(|(_, current)| rank(signal) > rank(current))
+  };
+
+  for link in data.links {
+    let Some(target) = resolve(&link) else {
+      content_links.push(link);
+      continue;
+    };
+    let hint = hints.get(&link);
+
+    let has_rel = |values: &[&str]| {
+      hint.and_then(|hint| hint.rel.as_ref()).is_some_and(|rel| {
+        rel
+          .iter()
+          .any(|token| values.iter().any(|value| token.eq_ignore_ascii_case(value)))
+      })
+    };
+    let has_label = |label: &Regex| {
+      hint
+        .into_iter()
+        .flat_map(|hint| [hint.aria_label.as_deref(), hint.anchor_text.as_deref()])
+        .flatten()
+        .any(|text| label.is_match(&text.split_whitespace().collect::<Vec<_>>().join(" ")))
+    };
+
+    let (link_series, link_page) = pagination_series(&target);
+    let link_page = link_page.filter(|_| link_series == series);
+
+    let mut is_pagination = link_page.is_some();
+    for (is_next, rel, label) in [
+      (true, &["next"][..], &*NEXT_LABEL),
+      (false, &["prev", "previous"][..], &*PREV_LABEL),
+    ] {
+      let signal = if has_rel(rel) {
+        Some(PAGINATION_SIGNAL_REL)
+      } else if has_label(label) {
+        Some(PAGINATION_SIGNAL_LABEL)
+      } else {
+        None
+      };
+      let Some(signal) = signal else {
+        continue;
+      };
+      is_pagination = true;
+      let slot = if is_next { &mut next } else { &mut prev };
+      if better(slot, signal) {
+        *slot = Some((target.clone(), signal));
+      }
+    }
+
+    if let Some(link_page) = link_page {
+      if page.checked_add(1) == Some(link_page) && better(&next, PAGINATION_SIGNAL_PATTERN) {
+        next = Some((target.clone(), PAGINATION_SIGNAL_PATTERN));
+      }
+      if link_page.checked_add(1) == Some(page) && better(&prev, PAGINATION_SIGNAL_PATTERN) {
+        prev = Some((target.clone(), PAGINATION_SIGNAL_PATTERN));
+      }
+      numbered.push((target.to_string(), link_page));
+    }
+
+    if is_pagination {
+      pagination_links.push(link);
+    } else {
+      content_links.push(link);
+    }
+  }
+
+  // Without a page number on the first page, a plain link back to the series is page 1.
+  let page_of = |target: &Url, fallback: u32| {
+    let (link_series, link_page) = pagination_series(target);
+    match link_page {
+      Some(link_page) if link_series == series => link_page,
+      None if link_series == series => 1,
+      _ => fallback,
+    }
+  };
+
+  let mut chain: BTreeMap<u32, String> = numbered
+    .into_iter()
+    .rev()
+    .map(|(url, page)| (page, url))
+    .collect();
+  chain.insert(page, url.to_string());
+  if let Some((target, _)) = &next {
+    chain
+      .entry(page_of(target, page.saturating_add(1)))
+      .or_insert_with(|| target.to_string());
+  }
+  if let Some((target, _)) = prev.as_ref().filter(|_| page > 1) {
+    chain
+      .entry(page_of(target, page - 1))
+      .or_insert_with(|| target.to_string());
+  }
+  Ok(PaginationResult {
+    page,
+    next_signal: next.as_ref().map(|(_, signal)| signal.to_string()),
+    next: next.map(|(target, _)| target.into()),
+    prev: prev.map(|(target, _)| target.into()),
+    chain: chain
+      .into_iter()
+      .map(|(page, url)| PaginationPage { url, page })
+      .collect(),
+    pagination_links,
+    content_links,
+  })
+}
+
+/// Detect "next page" links and the pagination chain of a listing page, and separate
+/// pagination links from content links.
+#[napi]
+pub async fn detect_pagination(data: DetectPaginationCall) -> Result<PaginationResult> {
+  let res = task::spawn_blocking(move || _detect_pagination(data))
+    .await
+    .map_err(|e| {
+      napi::Error::new(
+        napi::Status::GenericFailure,
+        format!("detect_pagination join error: {e}"),
+      )
+    })?;
+
+  res.map_err(|e| {
+    Error::new(
+      Status::GenericFailure,
+      format!("Detect pagination error: {e}"),
+    )
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_detect_pagination() {
+    let hint = |rel: &[&str], anchor_text: &str, aria_label: Option<&str>| LinkHint {
+      rel: Some(rel.iter().map(|rel| rel.to_string()).collect()),
+      anchor_text: Some(anchor_text.to_string()),
+      aria_label: aria_label.map(str::to_string),
+      ..Default::default()
+    };
+
+    let res = _detect_pagination(DetectPaginationCall {
+      url: "https://example.com/blog?page=3&tag=rust".to_string(),
+      links: vec![
+        "/blog?tag=rust".to_string(),
+        "/blog?page=2&tag=rust".to_string(),
+        "/blog?page=4&tag=rust".to_string(),
+        "/blog?page=5&tag=rust".to_string(),
+        "/blog/hello-world".to_string(),
+        "/blog?page=2&tag=go".to_string(),
+      ],
+      link_hints: None,
+      rel_next: None,
+      rel_prev: None,
+    })
+    .unwrap();
+
+    assert_eq!(res.page, 3);
+    assert_eq!(
+      res.next.as_deref(),
+      Some("https://example.com/blog?page=4&tag=rust")
+    );
+    assert_eq!(res.next_signal.as_deref(), Some(PAGINATION_SIGNAL_PATTERN));
+    assert_eq!(
+      res.prev.as_deref(),
+      Some("https://example.com/blog?page=2&tag=rust")
+    );
+    assert_eq!(
+      res.chain.iter().map(|page| page.page).collect::<Vec<_>>(),
+      vec![2, 3, 4, 5]
+    );
+    assert_eq!(
+      res.pagination_links,
+      vec![
+        "/blog?page=2&tag=rust",
+        "/blog?page=4&tag=rust",
+        "/blog?page=5&tag=rust",
+      ]
+    );
+    assert_eq!(
+      res.content_links,
+      vec!["/blog?tag=rust", "/blog/hello-world", "/blog?page=2&tag=go"]
+    );
+
+    // `rel` and labels beat numbering, and path page numbers work the same as query ones.
+    let res = _detect_pagination(DetectPaginationCall {
+      url: "https://example.com/news/page/2/".to_string(),
+      links: vec![
+        "/news/page/3/".to_string(),
+        "/news/archive-2019".to_string(),
+        "/news/".to_string(),
+        "/news/2019/launch".to_string(),
+      ],
+      link_hints: Some(HashMap::from([
+        (
+          "/news/archive-2019".to_string(),
+          hint(&[], "", Some("Next page")),
+        ),
+        ("/news/".to_string(), hint(&[], "« Previous", None)),
+        (
+          "/news/2019/launch".to_string(),
+          hint(&["bookmark"], "Launch", None),
+        ),
+      ])),
+      rel_next: None,
+      rel_prev: None,
+    })
+    .unwrap();
+
+    assert_eq!(res.page, 2);
+    assert_eq!(
+      res.next.as_deref(),
+      Some("https://example.com/news/archive-2019")
+    );
+    assert_eq!(res.next_signal.as_deref(), Some(PAGINATION_SIGNAL_LABEL));
+    assert_eq!(res.prev.as_deref(), Some("https://example.com/news/"));
+    assert_eq!(
+      res.chain,
+      vec![
+        PaginationPage {
+          url: "https://example.com/news/".to_string(),
+          page: 1,
+        },
+        PaginationPage {
+          url: "https://example.com/news/page/2/".to_string(),
+          page: 2,
+        },
+        PaginationPage {
+          url: "https://example.com/news/page/3/".to_string(),
+          page: 3,
+        },
+      ]
+    );
+    assert_eq!(res.content_links, vec!["/news/2019/launch"]);
+
+    let res = _detect_pagination(DetectPaginationCall {
+      url: "https://example.com/news/page/2/".to_string(),
+      links: vec!["/news/page/3/".to_string()],
+      link_hints: None,
+      rel_next: Some("/news/page/3/?sort=new".to_string()),
+      rel_prev: None,
+    })
+    .unwrap();
+    assert_eq!(
+      res.next.as_deref(),
+      Some("https://example.com/news/page/3/?sort=new")
+    );
+    assert_eq!(res.next_signal.as_deref(), Some(PAGINATION_SIGNAL_REL));
+
+    // Page numbers at the top of the range don't overflow.
+    let res = _detect_pagination(DetectPaginationCall {
+      url: "https://example.com/list?page=4294967295".to_string(),
+      links: vec![
+        "/list?page=4294967294".to_string(),
+        "/list?page=4294967295".to_string(),
+      ],
+      link_hints: None,
+      rel_next: Some("/list?cursor=abc".to_string()),
+      rel_prev: None,
+    })
+    .unwrap();
+    assert_eq!(res.page, u32::MAX);
+    assert_eq!(
+      res.prev.as_deref(),
+      Some("https://example.com/list?page=4294967294")
+    );
+    assert_eq!(res.chain.len(), 2);
+  }
+}
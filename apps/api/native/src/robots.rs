@@ -0,0 +1,491 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use texting_robots::Robot;
+use tokio::task;
+use url::Url;
+
+#[derive(Deserialize, Default)]
+#[napi(object)]
+pub struct ParseRobotsTxtCall {
+  pub robots_txt: String,
+  /// User agents to evaluate, in priority order. Defaults to the Firecrawl agent.
+  pub user_agents: Option<Vec<String>>,
+  /// URLs (absolute, or paths starting with `/`) to explain against the matched group.
+  pub urls: Option<Vec<String>>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+#[napi(object)]
+pub struct RobotsRule {
+  /// `allow` or `disallow`.
+  pub directive: String,
+  pub pattern: String,
+  /// 1-based line number in robots.txt.
+  pub line: u32,
+}
+
+#[derive(Serialize, Debug)]
+#[napi(object)]
+pub struct RobotsUrlVerdict {
+  pub url: String,
+  pub allowed: bool,
+  /// The rule that decided the verdict, if any matched.
+  pub rule: Option<RobotsRule>,
+}
+
+#[derive(Serialize, Debug)]
+#[napi(object)]
+pub struct RobotsTxtAnalysis {
+  pub sitemaps: Vec<String>,
+  pub crawl_delay: Option<f64>,
+  /// Agent whose group applies: one of the requested agents, `*`, or none if no group matched.
+  pub matched_user_agent: Option<String>,
+  /// Rules of the applicable group(s), in file order.
+  pub rules: Vec<RobotsRule>,
+  pub url_verdicts: Vec<RobotsUrlVerdict>,
+}
+
+/// Builds the robots.txt gate for the first of `user_agents` that robots.txt has its own group
+/// for, falling back to the first agent (which then gets the `*` group). Also returns the
+/// group that applied, parsed once up front to explain `ROBOTS_TXT` denials.
+pub(crate) fn build_robot(
+  robots_txt: &str,
+  user_agents: Option<&[String]>,
+) -> Option<(Robot, Option<RobotsGroupMatch>)> {
+  let agents: Vec<&str> = match user_agents {
+    Some(agents) if !agents.is_empty() => agents.iter().map(String::as_str).collect(),
+    _ => DEFAULT_ROBOTS_USER_AGENTS.to_vec(),
+  };
+
+  let group = RobotsTxt::parse(robots_txt).match_group(&agents);
+  let preferred = group
+    .as_ref()
+    .map(|group| group.user_agent.as_str())
+    .filter(|agent| *agent != "*");
+
+  let robot = preferred
+    .into_iter()
+    .chain(agents.iter().copied())
+    .find_map(|agent| Robot::new(agent, robots_txt.as_bytes()).ok())?;
+
+  Some((robot, group))
+}
+
+pub(crate) static DEFAULT_ROBOTS_USER_AGENTS: &[&str] = &["FireCrawlAgent", "FirecrawlAgent"];
+
+#[derive(Default)]
+struct RobotsGroup {
+  user_agents: Vec<String>,
+  rules: Vec<RobotsRule>,
+  crawl_delay: Option<f64>,
+}
+
+/// robots.txt parsed into user-agent groups, following RFC 9309 matching semantics.
+#[derive(Default)]
+struct RobotsTxt {
+  groups: Vec<RobotsGroup>,
+  sitemaps: Vec<String>,
+}
+
+/// The merged group(s) that apply to a crawler.
+pub(crate) struct RobotsGroupMatch {
+  pub(crate) user_agent: String,
+  rules: Vec<RobotsRule>,
+  crawl_delay: Option<f64>,
+}
+
+/// Percent-encodes what URL serialization encodes in a path (controls, space, `"<>`{}` and
+/// non-ASCII), so patterns compare against serialized URL paths. Escapes are normalized with
+/// `normalize_robots_escapes`.
+fn encode_robots_pattern(pattern: &str) -> String {
+  let mut out = String::with_capacity(pattern.len());
+  for ch in pattern.chars() {
+    if ch.is_ascii_graphic() && !matches!(ch, '"' | '<' | '>' | '`' | '{' | '}') {
+      out.push(ch);
+    } else {
+      let mut buf = [0u8; 4];
+      for byte in ch.encode_utf8(&mut buf).bytes() {
+        out.push_str(&format!("%{byte:02X}"));
+      }
+    }
+  }
+  normalize_robots_escapes(&out)
+}
+
+/// Decodes escaped unreserved characters and uppercases other escapes, so `%7e`, `%7E` and
+/// `~` compare equal as RFC 9309 requires.
+fn normalize_robots_escapes(value: &str) -> String {
+  let mut out = String::with_capacity(value.len());
+  let mut rest = value;
+  while let Some(index) = rest.find('%') {
+    out.push_str(&rest[..index]);
+    let escape = &rest[index..];
+    let byte = escape
+      .get(1..3)
+      .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+    match byte {
+      Some(byte) if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) => {
+        out.push(byte as char);
+      }
+      Some(byte) => out.push_str(&format!("%{byte:02X}")),
+      None => {
+        out.push('%');
+        rest = &escape[1..];
+        continue;
+      }
+    }
+    rest = &escape[3..];
+  }
+  out.push_str(rest);
+  out
+}
+
+/// Matches a robots.txt path pattern (`*` wildcards, trailing `$` anchor) from the start of `path`.
+fn robots_pattern_matches(pattern: &str, path: &str) -> bool {
+  let (pattern, anchored) = match pattern.strip_suffix('$') {
+    Some(pattern) => (pattern, true),
+    None => (pattern, false),
+  };
+
+  let mut parts = pattern.split('*');
+  let first = parts.next().unwrap_or_default();
+  if !path.starts_with(first) {
+    return false;
+  }
+
+  let rest: Vec<&str> = parts.collect();
+  let mut pos = first.len();
+  if rest.is_empty() {
+    return !anchored || pos == path.len();
+  }
+
+  for (i, part) in rest.iter().enumerate() {
+    if anchored && i == rest.len() - 1 {
+      return path.len() >= pos + part.len() && path.ends_with(part);
+    }
+    match path[pos..].find(part) {
+      Some(idx) => pos += idx + part.len(),
+      None => return false,
+    }
+  }
+
+  true
+}
+
+impl RobotsTxt {
+  fn parse(content: &str) -> Self {
+    let mut robots = RobotsTxt::default();
+    let mut current: Option<RobotsGroup> = None;
+    let mut in_user_agent_block = false;
+
+    // CRLF, a lone CR and LF all end a line.
+    let lines = content
+      .trim_start_matches('\u{feff}')
+      .split('\n')
+      .flat_map(|line| line.strip_suffix('\r').unwrap_or(line).split('\r'));
+    for (index, raw_line) in lines.enumerate() {
+      let line = raw_line.split('#').next().unwrap_or_default().trim();
+      let Some((key, value)) = line.split_once(':') else {
+        continue;
+      };
+      let key = key.trim().to_ascii_lowercase();
+      let value = value.trim();
+
+      match key.as_str() {
+        "user-agent" | "useragent" => {
+          if !in_user_agent_block {
+            robots.groups.extend(current.take());
+            current = Some(RobotsGroup::default());
+          }
+          in_user_agent_block = true;
+          if let Some(group) = current.as_mut() {
+            // Only the product token counts: "Googlebot/2.1" matches "googlebot".
+            let token = value.split(['/', ' ']).next().unwrap_or_default();
+            group.user_agents.push(token.to_ascii_lowercase());
+          }
+        }
+        "allow" | "disallow" => {
+          in_user_agent_block = false;
+          if let Some(group) = current.as_mut() {
+            if !value.is_empty() {
+              group.rules.push(RobotsRule {
+                directive: key,
+                pattern: encode_robots_pattern(value),
+                line: index as u32 + 1,
+              });
+            }
+          }
+        }
+        "crawl-delay" => {
+          in_user_agent_block = false;
+          if let Some(group) = current.as_mut() {
+            group.crawl_delay = group.crawl_delay.or_else(|| {
+              value
+                .parse::<f64>()
+                .ok()
+                .filter(|d| d.is_finite() && *d >= 0.0)
+            });
+          }
+        }
+        "sitemap" if !value.is_empty() => robots.sitemaps.push(value.to_string()),
+        _ => {}
+      }
+    }
+
+    robots.groups.extend(current);
+    robots
+  }
+
+  /// Picks the groups for the first agent (in priority order) that has its own group,
+  /// falling back to the `*` groups. All groups naming the same agent are merged.
+  fn match_group<S: AsRef<str>>(&self, user_agents: &[S]) -> Option<RobotsGroupMatch> {
+    let group_for = |agent: &str, user_agent: &str| -> Option<RobotsGroupMatch> {
+      let groups: Vec<&RobotsGroup> = self
+        .groups
+        .iter()
+        .filter(|group| group.user_agents.iter().any(|ua| ua == agent))
+        .collect();
+      (!groups.is_empty()).then(|| RobotsGroupMatch {
+        user_agent: user_agent.to_string(),
+        rules: groups
+          .iter()
+          .flat_map(|group| group.rules.iter().cloned())
+          .collect(),
+        crawl_delay: groups.iter().find_map(|group| group.crawl_delay),
+      })
+    };
+
+    user_agents
+      .iter()
+      .find_map(|agent| group_for(&agent.as_ref().to_ascii_lowercase(), agent.as_ref()))
+      .or_else(|| group_for("*", "*"))
+  }
+}
+
+impl RobotsGroupMatch {
+  /// Longest matching pattern wins; `allow` wins ties. Returns the deciding rule, if any.
+  pub(crate) fn decide(&self, path: &str) -> (bool, Option<&RobotsRule>) {
+    if path == "/robots.txt" {
+      return (true, None);
+    }
+
+    let rule = self
+      .rules
+      .iter()
+      .filter(|rule| robots_pattern_matches(&rule.pattern, path))
+      .max_by_key(|rule| (rule.pattern.len(), rule.directive == "allow"));
+
+    match rule {
+      Some(rule) => (rule.directive == "allow", Some(rule)),
+      None => (true, None),
+    }
+  }
+}
+
+/// Path and query of a URL as robots.txt rules see it.
+pub(crate) fn robots_path(url: &str) -> String {
+  match Url::parse(url) {
+    Ok(url) => normalize_robots_escapes(&match url.query() {
+      Some(query) => format!("{}?{}", url.path(), query),
+      None => url.path().to_string(),
+    }),
+    Err(_) => encode_robots_pattern(url),
+  }
+}
+
+pub(crate) fn _parse_robots_txt(data: ParseRobotsTxtCall) -> RobotsTxtAnalysis {
+  let robots = RobotsTxt::parse(&data.robots_txt);
+  let user_agents = data.user_agents.unwrap_or_else(|| {
+    DEFAULT_ROBOTS_USER_AGENTS
+      .iter()
+      .map(|agent| agent.to_string())
+      .collect()
+  });
+  let group = robots.match_group(&user_agents);
+
+  let url_verdicts = data
+    .urls
+    .unwrap_or_default()
+    .into_iter()
+    .map(|url| {
+      let (allowed, rule) = match group.as_ref() {
+        Some(group) => group.decide(&robots_path(&url)),
+        None => (true, None),
+      };
+      RobotsUrlVerdict {
+        url,
+        allowed,
+        rule: rule.cloned(),
+      }
+    })
+    .collect();
+
+  RobotsTxtAnalysis {
+    crawl_delay: group.as_ref().and_then(|group| group.crawl_delay),
+    matched_user_agent: group.as_ref().map(|group| group.user_agent.clone()),
+    rules: group.map(|group| group.rules).unwrap_or_default(),
+    url_verdicts,
+    sitemaps: robots.sitemaps,
+  }
+}
+
+/// Parse robots.txt into its sitemaps, crawl-delay and matched user-agent group, and explain
+/// which rule allows or blocks each of the given URLs.
+#[napi]
+pub async fn parse_robots_txt(data: ParseRobotsTxtCall) -> Result<RobotsTxtAnalysis> {
+  task::spawn_blocking(move || _parse_robots_txt(data))
+    .await
+    .map_err(|e| {
+      napi::Error::new(
+        napi::Status::GenericFailure,
+        format!("parse_robots_txt join error: {e}"),
+      )
+    })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_robots_txt_groups_and_directives() {
+    let robots_txt = "# comment\n\
+      Sitemap: https://example.com/sitemap.xml\n\
+      User-agent: Googlebot\n\
+      Disallow: /\n\
+      \n\
+      User-agent: *\n\
+      Crawl-delay: 2.5\n\
+      Disallow: /private\n\
+      Allow: /private/public\n\
+      Disallow: /*.pdf$\n\
+      \n\
+      User-agent: FirecrawlAgent\n\
+      User-agent: OtherBot\n\
+      Disallow: /firecrawl-only\n\
+      Crawl-delay: 1\n\
+      Sitemap: https://example.com/news.xml\n";
+
+    let result = _parse_robots_txt(ParseRobotsTxtCall {
+      robots_txt: robots_txt.to_string(),
+      user_agents: None,
+      urls: Some(vec![
+        "https://example.com/firecrawl-only/x".to_string(),
+        "/private".to_string(),
+      ]),
+    });
+    assert_eq!(
+      result.sitemaps,
+      vec![
+        "https://example.com/sitemap.xml",
+        "https://example.com/news.xml"
+      ]
+    );
+    assert_eq!(result.matched_user_agent.as_deref(), Some("FireCrawlAgent"));
+    assert_eq!(result.crawl_delay, Some(1.0));
+    assert_eq!(result.rules.len(), 1);
+    assert!(!result.url_verdicts[0].allowed);
+    assert_eq!(result.url_verdicts[0].rule.as_ref().unwrap().line, 14);
+    assert!(result.url_verdicts[1].allowed);
+    assert!(result.url_verdicts[1].rule.is_none());
+
+    let result = _parse_robots_txt(ParseRobotsTxtCall {
+      robots_txt: robots_txt.to_string(),
+      user_agents: Some(vec!["SomeBot".to_string()]),
+      urls: Some(vec![
+        "https://example.com/private/page".to_string(),
+        "https://example.com/private/public/page".to_string(),
+        "https://example.com/files/a.pdf".to_string(),
+        "https://example.com/files/a.pdf?x=1".to_string(),
+      ]),
+    });
+    assert_eq!(result.matched_user_agent.as_deref(), Some("*"));
+    assert_eq!(result.crawl_delay, Some(2.5));
+    let verdicts = &result.url_verdicts;
+    assert!(!verdicts[0].allowed);
+    assert_eq!(verdicts[0].rule.as_ref().unwrap().pattern, "/private");
+    assert!(verdicts[1].allowed);
+    assert_eq!(verdicts[1].rule.as_ref().unwrap().directive, "allow");
+    assert!(!verdicts[2].allowed);
+    assert!(verdicts[3].allowed);
+  }
+
+  #[test]
+  fn test_parse_robots_txt_without_matching_group() {
+    let result = _parse_robots_txt(ParseRobotsTxtCall {
+      robots_txt: "User-agent: Googlebot\nDisallow: /".to_string(),
+      user_agents: None,
+      urls: Some(vec!["https://example.com/".to_string()]),
+    });
+    assert!(result.matched_user_agent.is_none());
+    assert!(result.crawl_delay.is_none());
+    assert!(result.url_verdicts[0].allowed);
+  }
+
+  #[test]
+  fn test_robots_pattern_matches() {
+    assert!(robots_pattern_matches("/fish", "/fish.html"));
+    assert!(robots_pattern_matches("/fish*", "/fishheads/yummy.html"));
+    assert!(!robots_pattern_matches("/fish", "/Fish.asp"));
+    assert!(robots_pattern_matches(
+      "/*.php",
+      "/folder/filename.php?parameters"
+    ));
+    assert!(robots_pattern_matches("/*.php$", "/filename.php"));
+    assert!(!robots_pattern_matches("/*.php$", "/filename.php/"));
+    assert!(robots_pattern_matches(
+      "/fish*.php",
+      "/fishheads/catfish.php?parameters"
+    ));
+    assert!(robots_pattern_matches("/$", "/"));
+    assert!(!robots_pattern_matches("/$", "/page"));
+    assert!(robots_pattern_matches(
+      &encode_robots_pattern("/\u{a0}test"),
+      Url::parse("https://example.com/\u{a0}test").unwrap().path()
+    ));
+  }
+
+  #[test]
+  fn test_parse_robots_txt_line_endings_and_encoding() {
+    let urls = |urls: &[&str]| Some(urls.iter().map(|url| url.to_string()).collect());
+
+    for robots_txt in [
+      "User-agent: *\nDisallow: /a b\nDisallow: /%7euser\n",
+      "User-agent: *\r\nDisallow: /a b\r\nDisallow: /%7euser\r\n",
+      "User-agent: *\rDisallow: /a b\rDisallow: /%7euser\r",
+    ] {
+      let result = _parse_robots_txt(ParseRobotsTxtCall {
+        robots_txt: robots_txt.to_string(),
+        user_agents: None,
+        urls: urls(&[
+          "https://example.com/a b",
+          "https://example.com/a%20b/c",
+          "https://example.com/~user/page",
+          "https://example.com/%7Euser",
+          "https://example.com/ab",
+        ]),
+      });
+      let lines: Vec<_> = result
+        .url_verdicts
+        .iter()
+        .map(|verdict| verdict.rule.as_ref().map(|rule| rule.line))
+        .collect();
+      assert_eq!(
+        lines,
+        vec![Some(2), Some(2), Some(3), Some(3), None],
+        "{robots_txt:?}"
+      );
+      assert_eq!(result.rules[0].pattern, "/a%20b");
+      assert_eq!(result.rules[1].pattern, "/~user");
+    }
+
+    assert_eq!(normalize_robots_escapes("/%2f%41%zz%"), "/%2FA%zz%");
+    assert!(robots_pattern_matches("/*", "/"));
+    assert!(robots_pattern_matches("/a*$", "/a/b"));
+    assert!(robots_pattern_matches("/a**b$", "/ab"));
+    // Only a trailing `$` anchors.
+    assert!(robots_pattern_matches("/a$b", "/a$b/c"));
+    assert!(!robots_pattern_matches("/a$", "/a/"));
+  }
+}
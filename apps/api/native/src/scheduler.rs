@@ -0,0 +1,487 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use serde::{Deserialize, Serialize};
+use std::{
+  collections::HashMap,
+  time::{SystemTime, UNIX_EPOCH},
+};
+use url::Url;
+
+use crate::crawler::{normalize_host, url_host};
+
+const IN_FLIGHT: &str = "IN_FLIGHT";
+const CONCURRENCY: &str = "CONCURRENCY";
+const HOST_CONCURRENCY: &str = "HOST_CONCURRENCY";
+const CRAWL_DELAY: &str = "CRAWL_DELAY";
+const RATE_LIMIT: &str = "RATE_LIMIT";
+
+/// Crawl-delays above this are treated as this, so one host can't stall a crawl for hours.
+const DEFAULT_MAX_CRAWL_DELAY_MS: f64 = 60_000.0;
+
+#[derive(Deserialize, Default)]
+#[napi(object)]
+pub struct CrawlSchedulerOptions {
+  /// Minimum time between dispatches to the same host, in milliseconds. A longer robots.txt
+  /// `Crawl-delay` set with `set_crawl_delay` takes precedence. Defaults to 0.
+  pub delay_ms: Option<f64>,
+  /// Upper bound applied to robots.txt `Crawl-delay`, in milliseconds. Defaults to 60 seconds.
+  pub max_crawl_delay_ms: Option<f64>,
+  /// URLs in flight per host. Defaults to 1. When URLs come from a `CrawlFrontier`, see its
+  /// `max_in_flight_per_host` for how the two limits combine.
+  pub max_concurrency_per_host: Option<u32>,
+  /// URLs in flight across all hosts. Unlimited by default.
+  pub max_concurrency: Option<u32>,
+  /// Dispatches per second across all hosts. Unlimited by default.
+  pub max_requests_per_second: Option<f64>,
+  /// Dispatches allowed at once after idling, when rate limited. Defaults to one second's worth.
+  pub burst: Option<u32>,
+}
+
+#[derive(Serialize, Debug)]
+#[napi(object)]
+pub struct DeferredUrl {
+  pub url: String,
+  /// What holds it back: `IN_FLIGHT` if the URL itself hasn't been `complete`d yet,
+  /// `CONCURRENCY`, `HOST_CONCURRENCY`, `CRAWL_DELAY` or `RATE_LIMIT`.
+  pub reason: String,
+  /// Soonest time, in epoch milliseconds, the URL can be dispatched. Not known when it waits
+  /// for an in-flight URL to `complete`.
+  pub not_before: Option<f64>,
+}
+
+#[derive(Serialize, Debug)]
+#[napi(object)]
+pub struct ScheduleResult {
+  /// URLs to dispatch now, in the order given. They count as in flight until `complete`d.
+  pub ready: Vec<String>,
+  pub deferred: Vec<DeferredUrl>,
+  /// URLs without a host, which can't be scheduled.
+  pub invalid: Vec<String>,
+  /// Earliest `not_before` among `deferred`, for the caller's next wake-up.
+  pub next_ready_at: Option<f64>,
+}
+
+#[derive(Default)]
+struct HostState {
+  in_flight: u32,
+  /// When the host may next be dispatched to, in epoch milliseconds.
+  next_allowed_at: f64,
+  crawl_delay_ms: Option<f64>,
+}
+
+/// Token bucket for the global rate limit.
+struct RateLimiter {
+  /// Tokens per millisecond.
+  rate: f64,
+  capacity: f64,
+  tokens: f64,
+  updated_at: Option<f64>,
+}
+
+impl RateLimiter {
+  fn refill(&mut self, now: f64) {
+    if let Some(updated_at) = self.updated_at {
+      let elapsed = (now - updated_at).max(0.0);
+      self.tokens = (self.tokens + elapsed * self.rate).min(self.capacity);
+    }
+    self.updated_at = Some(now);
+  }
+
+  /// Takes a token, or returns when one will be available if `waiting` others are queued
+  /// ahead for it.
+  fn acquire(&mut self, now: f64, waiting: u32) -> std::result::Result<(), f64> {
+    if waiting == 0 && self.tokens >= 1.0 {
+      self.tokens -= 1.0;
+      return Ok(());
+    }
+    Err(now + (waiting as f64 + 1.0 - self.tokens) / self.rate)
+  }
+}
+
+fn system_now() -> f64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map_or(0.0, |elapsed| elapsed.as_secs_f64() * 1000.0)
+}
+
+/// Decides which URLs can be dispatched now under per-host delays and concurrency caps and a
+/// global rate limit. Every method takes `now` in epoch milliseconds, defaulting to the system
+/// clock, so callers and tests can drive time themselves.
+#[napi]
+pub struct CrawlScheduler {
+  delay_ms: f64,
+  max_crawl_delay_ms: f64,
+  max_concurrency_per_host: u32,
+  max_concurrency: Option<u32>,
+  rate_limiter: Option<RateLimiter>,
+  /// Hosts with a crawl-delay, URLs in flight or a delay still running; idle ones are dropped.
+  hosts: HashMap<String, HostState>,
+  /// Host of each in-flight URL, keyed by the serialized URL.
+  in_flight: HashMap<String, String>,
+}
+
+impl CrawlScheduler {
+  fn host_delay_ms(&self, host: &HostState) -> f64 {
+    host.crawl_delay_ms.map_or(self.delay_ms, |crawl_delay| {
+      crawl_delay.min(self.max_crawl_delay_ms).max(self.delay_ms)
+    })
+  }
+
+  fn schedule_at(&mut self, urls: Vec<String>, now: f64) -> ScheduleResult {
+    let mut ready = Vec::new();
+    let mut deferred = Vec::new();
+    let mut invalid = Vec::new();
+    let mut rate_limited = 0;
+
+    if let Some(rate_limiter) = &mut self.rate_limiter {
+      rate_limiter.refill(now);
+    }
+    self.hosts.retain(|_, state| {
+      state.crawl_delay_ms.is_some() || state.in_flight > 0 || state.next_allowed_at > now
+    });
+
+    for url in urls {
+      let Some((key, host)) = Url::parse(&url)
+        .ok()
+        .filter(|parsed| parsed.host_str().is_some())
+        .map(|parsed| (parsed.to_string(), url_host(&parsed).to_string()))
+      else {
+        invalid.push(url);
+        continue;
+      };
+
+      let defer = |reason: &str, not_before: Option<f64>| DeferredUrl {
+        url: url.clone(),
+        reason: reason.to_string(),
+        not_before,
+      };
+
+      if self.in_flight.contains_key(&key) {
+        deferred.push(defer(IN_FLIGHT, None));
+        continue;
+      }
+
+      if self
+        .max_concurrency
+        .is_some_and(|max| self.in_flight.len() as u32 >= max)
+      {
+        deferred.push(defer(CONCURRENCY, None));
+        continue;
+      }
+
+      let state = self.hosts.entry(host.clone()).or_default();
+      if state.in_flight >= self.max_concurrency_per_host {
+        deferred.push(defer(HOST_CONCURRENCY, None));
+        continue;
+      }
+      if state.next_allowed_at > now {
+        let not_before = state.next_allowed_at;
+        deferred.push(defer(CRAWL_DELAY, Some(not_before)));
+        continue;
+      }
+
+      if let Some(rate_limiter) = &mut self.rate_limiter {
+        if let Err(not_before) = rate_limiter.acquire(now, rate_limited) {
+          rate_limited += 1;
+          deferred.push(defer(RATE_LIMIT, Some(not_before)));
+          continue;
+        }
+      }
+
+      let delay_ms = self.host_delay_ms(&self.hosts[&host]);
+      let state = self
+        .hosts
+        .get_mut(&host)
+        .expect("host state was just created");
+      state.in_flight += 1;
+      state.next_allowed_at = now + delay_ms;
+      self.in_flight.insert(key, host);
+      ready.push(url);
+    }
+
+    let next_ready_at = deferred
+      .iter()
+      .filter_map(|url| url.not_before)
+      .min_by(f64::total_cmp);
+
+    ScheduleResult {
+      ready,
+      deferred,
+      invalid,
+      next_ready_at,
+    }
+  }
+}
+
+#[napi]
+impl CrawlScheduler {
+  #[napi(constructor)]
+  pub fn new(options: Option<CrawlSchedulerOptions>) -> Result<Self> {
+    let options = options.unwrap_or_default();
+
+    let max_crawl_delay_ms = options
+      .max_crawl_delay_ms
+      .unwrap_or(DEFAULT_MAX_CRAWL_DELAY_MS);
+    if max_crawl_delay_ms.is_nan() || max_crawl_delay_ms < 0.0 {
+      return Err(Error::new(
+        Status::GenericFailure,
+        format!(
+          "Crawl scheduler error: max_crawl_delay_ms must be a non-negative number, got {max_crawl_delay_ms}"
+        ),
+      ));
+    }
+
+    let rate_limiter = match options.max_requests_per_second {
+      Some(rate) if !(rate > 0.0 && rate.is_finite()) => {
+        return Err(Error::new(
+          Status::GenericFailure,
+          format!("Crawl scheduler error: max_requests_per_second must be positive, got {rate}"),
+        ))
+      }
+      Some(rate) => {
+        let capacity = options.burst.map_or(rate.ceil(), f64::from).max(1.0);
+        Some(RateLimiter {
+          rate: rate / 1000.0,
+          capacity,
+          tokens: capacity,
+          updated_at: None,
+        })
+      }
+      None => None,
+    };
+
+    Ok(Self {
+      delay_ms: options.delay_ms.unwrap_or(0.0).max(0.0),
+      max_crawl_delay_ms,
+      max_concurrency_per_host: options.max_concurrency_per_host.unwrap_or(1).max(1),
+      max_concurrency: options.max_concurrency,
+      rate_limiter,
+      hosts: HashMap::new(),
+      in_flight: HashMap::new(),
+    })
+  }
+
+  /// Set the robots.txt `Crawl-delay` for `host`, in seconds as written there, or clear it.
+  /// `host` is normalized like URL hosts, so IDN hosts may be given in Unicode.
+  #[napi]
+  pub fn set_crawl_delay(&mut self, host: String, crawl_delay: Option<f64>) -> Result<()> {
+    let Some(host) = normalize_host(&host) else {
+      return Err(Error::new(
+        Status::GenericFailure,
+        format!("Crawl scheduler error: Invalid host {host:?}"),
+      ));
+    };
+    self.hosts.entry(host).or_default().crawl_delay_ms = crawl_delay
+      .filter(|delay| *delay > 0.0)
+      .map(|delay| delay * 1000.0);
+    Ok(())
+  }
+
+  /// Split `urls` into those that can be dispatched now and those that have to wait.
+  #[napi]
+  pub fn schedule(&mut self, urls: Vec<String>, now: Option<f64>) -> ScheduleResult {
+    self.schedule_at(urls, now.unwrap_or_else(system_now))
+  }
+
+  /// Mark a URL returned as ready as done, freeing its host's slot. Returns false if it wasn't
+  /// in flight.
+  #[napi]
+  pub fn complete(&mut self, url: String) -> bool {
+    let key = Url::parse(&url).map_or(url, String::from);
+    let Some(host) = self.in_flight.remove(&key) else {
+      return false;
+    };
+    if let Some(state) = self.hosts.get_mut(&host) {
+      state.in_flight = state.in_flight.saturating_sub(1);
+    }
+    true
+  }
+
+  #[napi]
+  pub fn in_flight_count(&self) -> u32 {
+    self.in_flight.len() as u32
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn urls(urls: &[&str]) -> Vec<String> {
+    urls.iter().map(|url| url.to_string()).collect()
+  }
+
+  fn reasons(result: &ScheduleResult) -> Vec<(&str, Option<f64>)> {
+    result
+      .deferred
+      .iter()
+      .map(|url| (url.reason.as_str(), url.not_before))
+      .collect()
+  }
+
+  #[test]
+  fn test_crawl_scheduler_host_delay_and_concurrency() {
+    let mut scheduler = CrawlScheduler::new(Some(CrawlSchedulerOptions {
+      delay_ms: Some(500.0),
+      max_concurrency_per_host: Some(2),
+      ..Default::default()
+    }))
+    .unwrap();
+    scheduler
+      .set_crawl_delay("Slow.example.com".to_string(), Some(2.0))
+      .unwrap();
+
+    let result = scheduler.schedule_at(
+      urls(&[
+        "https://example.com/a",
+        "https://example.com/b",
+        "https://slow.example.com/a",
+        "mailto:someone@example.com",
+      ]),
+      1000.0,
+    );
+    assert_eq!(
+      result.ready,
+      vec!["https://example.com/a", "https://slow.example.com/a"]
+    );
+    assert_eq!(reasons(&result), vec![("CRAWL_DELAY", Some(1500.0))]);
+    assert_eq!(result.invalid, vec!["mailto:someone@example.com"]);
+    assert_eq!(result.next_ready_at, Some(1500.0));
+
+    let result = scheduler.schedule_at(
+      urls(&["https://example.com/b", "https://slow.example.com/b"]),
+      1500.0,
+    );
+    assert_eq!(result.ready, vec!["https://example.com/b"]);
+    assert_eq!(reasons(&result), vec![("CRAWL_DELAY", Some(3000.0))]);
+
+    // Both example.com slots are taken until one completes.
+    let result = scheduler.schedule_at(urls(&["https://example.com/c"]), 5000.0);
+    assert_eq!(reasons(&result), vec![("HOST_CONCURRENCY", None)]);
+    assert_eq!(result.next_ready_at, None);
+
+    assert!(scheduler.complete("https://example.com/a".to_string()));
+    assert!(!scheduler.complete("https://example.com/a".to_string()));
+    let result = scheduler.schedule_at(urls(&["https://example.com/c"]), 5000.0);
+    assert_eq!(result.ready, vec!["https://example.com/c"]);
+    assert_eq!(scheduler.in_flight_count(), 3);
+
+    // An in-flight URL isn't handed out again, nor does it take another slot.
+    assert!(scheduler.complete("https://example.com/b".to_string()));
+    let result = scheduler.schedule_at(
+      urls(&["https://example.com/c", "https://example.com/d"]),
+      9000.0,
+    );
+    assert_eq!(result.ready, vec!["https://example.com/d"]);
+    assert_eq!(reasons(&result), vec![("IN_FLIGHT", None)]);
+    assert_eq!(scheduler.in_flight_count(), 3);
+
+    // URLs are compared as parsed, so case in the host doesn't make another copy.
+    let result = scheduler.schedule_at(urls(&["https://EXAMPLE.com/d"]), 9500.0);
+    assert_eq!(reasons(&result), vec![("IN_FLIGHT", None)]);
+    assert!(scheduler.complete("https://Example.COM/d".to_string()));
+    assert_eq!(scheduler.in_flight_count(), 2);
+
+    // Hosts without a crawl-delay are forgotten once idle.
+    assert!(scheduler.complete("https://example.com/c".to_string()));
+    scheduler.schedule_at(Vec::new(), 20_000.0);
+    assert_eq!(
+      scheduler
+        .hosts
+        .keys()
+        .map(String::as_str)
+        .collect::<Vec<_>>(),
+      vec!["slow.example.com"]
+    );
+  }
+
+  #[test]
+  fn test_crawl_scheduler_caps_crawl_delay() {
+    let mut scheduler = CrawlScheduler::new(Some(CrawlSchedulerOptions {
+      max_crawl_delay_ms: Some(10_000.0),
+      max_concurrency_per_host: Some(5),
+      ..Default::default()
+    }))
+    .unwrap();
+    scheduler
+      .set_crawl_delay("example.com".to_string(), Some(3600.0))
+      .unwrap();
+
+    let result = scheduler.schedule_at(
+      urls(&["https://example.com/a", "https://example.com/b"]),
+      0.0,
+    );
+    assert_eq!(result.next_ready_at, Some(10_000.0));
+
+    scheduler
+      .set_crawl_delay("example.com".to_string(), None)
+      .unwrap();
+    let result = scheduler.schedule_at(urls(&["https://example.com/b"]), 10_000.0);
+    assert_eq!(result.ready.len(), 1);
+
+    // Hosts are keyed the way URL hosts are, IDNA-encoded.
+    scheduler
+      .set_crawl_delay("Bücher.example.".to_string(), Some(5.0))
+      .unwrap();
+    let result = scheduler.schedule_at(
+      urls(&[
+        "https://bücher.example/a",
+        "https://xn--bcher-kva.example/b",
+      ]),
+      0.0,
+    );
+    assert_eq!(reasons(&result), vec![("CRAWL_DELAY", Some(5000.0))]);
+    assert!(scheduler
+      .set_crawl_delay("exa mple.com".to_string(), Some(1.0))
+      .is_err());
+
+    for max_crawl_delay_ms in [-1.0, f64::NAN] {
+      assert!(CrawlScheduler::new(Some(CrawlSchedulerOptions {
+        max_crawl_delay_ms: Some(max_crawl_delay_ms),
+        ..Default::default()
+      }))
+      .is_err());
+    }
+  }
+
+  #[test]
+  fn test_crawl_scheduler_global_limits() {
+    let mut scheduler = CrawlScheduler::new(Some(CrawlSchedulerOptions {
+      max_requests_per_second: Some(2.0),
+      ..Default::default()
+    }))
+    .unwrap();
+
+    let batch = urls(&[
+      "https://a.com/",
+      "https://b.com/",
+      "https://c.com/",
+      "https://d.com/",
+    ]);
+    let result = scheduler.schedule_at(batch.clone(), 0.0);
+    assert_eq!(result.ready, vec!["https://a.com/", "https://b.com/"]);
+    assert_eq!(
+      reasons(&result),
+      vec![("RATE_LIMIT", Some(500.0)), ("RATE_LIMIT", Some(1000.0))]
+    );
+
+    let result = scheduler.schedule_at(batch[2..].to_vec(), 500.0);
+    assert_eq!(result.ready, vec!["https://c.com/"]);
+    assert_eq!(reasons(&result), vec![("RATE_LIMIT", Some(1000.0))]);
+
+    let mut scheduler = CrawlScheduler::new(Some(CrawlSchedulerOptions {
+      max_concurrency: Some(1),
+      ..Default::default()
+    }))
+    .unwrap();
+    let result = scheduler.schedule_at(batch, 0.0);
+    assert_eq!(result.ready, vec!["https://a.com/"]);
+    assert_eq!(result.deferred[0].reason, "CONCURRENCY");
+
+    assert!(CrawlScheduler::new(Some(CrawlSchedulerOptions {
+      max_requests_per_second: Some(0.0),
+      ..Default::default()
+    }))
+    .is_err());
+  }
+}
@@ -0,0 +1,225 @@
+use napi_derive::napi;
+use regex::Regex;
+use serde::Deserialize;
+use url::Url;
+
+use crate::crawler::{get_url_depth, LinkHint, PatternSet};
+
+/// How to rank allowed links. Each signal scores between 0 and 1 and is multiplied by its weight.
+#[derive(Deserialize, Default, Clone)]
+#[napi(object)]
+pub struct LinkScoringOptions {
+  /// What the crawl is looking for; its words are matched against URLs and anchor text.
+  pub prompt: Option<String>,
+  /// Extra keywords to match, on top of the prompt's.
+  pub keywords: Option<Vec<String>>,
+  /// Weight of shallow paths. Defaults to 1.
+  pub depth_weight: Option<f64>,
+  /// Weight of the link hint's `sitemap_priority`. Defaults to 1.
+  pub priority_weight: Option<f64>,
+  /// Weight of how specific the matching include pattern is. Defaults to 1.
+  pub pattern_weight: Option<f64>,
+  /// Weight of the share of keywords found in the link. Defaults to 2.
+  pub keyword_weight: Option<f64>,
+}
+
+/// Words too common in prompts to say anything about a link.
+static STOP_WORDS: &[&str] = &[
+  "about", "all", "and", "any", "are", "can", "for", "from", "get", "has", "have", "how", "into",
+  "its", "more", "not", "our", "page", "pages", "that", "the", "their", "them", "then", "there",
+  "these", "this", "those", "was", "what", "when", "where", "which", "who", "why", "will", "with",
+  "you", "your",
+];
+
+/// Lowercased words of at least three characters, split on anything that isn't alphanumeric.
+fn score_tokens(text: &str) -> impl Iterator<Item = String> + '_ {
+  text
+    .split(|c: char| !c.is_alphanumeric())
+    .filter(|token| token.chars().count() >= 3)
+    .map(str::to_lowercase)
+}
+
+/// How narrowly a pattern targets URLs, from 0 for `.*` towards 1 for long literals, based on
+/// the shortest text it can match.
+fn pattern_specificity(regex: &Regex) -> f64 {
+  let min_len = regex_syntax::parse(regex.as_str())
+    .ok()
+    .and_then(|hir| hir.properties().minimum_len())
+    .unwrap_or(0) as f64;
+  min_len / (min_len + 10.0)
+}
+
+/// `LinkScoringOptions` with defaults applied.
+pub(crate) struct LinkScorer {
+  keywords: Vec<String>,
+  /// `pattern_specificity` of each include pattern.
+  include_specificity: Vec<f64>,
+  depth_weight: f64,
+  priority_weight: f64,
+  pattern_weight: f64,
+  keyword_weight: f64,
+}
+
+impl LinkScorer {
+  pub(crate) fn new(options: &LinkScoringOptions, includes: &PatternSet) -> Self {
+    let mut keywords: Vec<String> = options
+      .prompt
+      .iter()
+      .chain(options.keywords.iter().flatten())
+      .flat_map(|text| score_tokens(text))
+      .filter(|token| !STOP_WORDS.contains(&token.as_str()))
+      .collect();
+    keywords.sort();
+    keywords.dedup();
+
+    Self {
+      keywords,
+      include_specificity: includes
+        .patterns
+        .iter()
+        .map(|pattern| pattern_specificity(&pattern.regex))
+        .collect(),
+      depth_weight: options.depth_weight.unwrap_or(1.0),
+      priority_weight: options.priority_weight.unwrap_or(1.0),
+      pattern_weight: options.pattern_weight.unwrap_or(1.0),
+      keyword_weight: options.keyword_weight.unwrap_or(2.0),
+    }
+  }
+
+  /// Weighted sum of signals that each fall in `0..=1`: shallow paths, sitemap priority, how
+  /// specific the matching include pattern is, and the share of keywords found in the URL or
+  /// anchor text.
+  pub(crate) fn score(&self, url: &Url, hint: Option<&LinkHint>, include: Option<usize>) -> f64 {
+    let depth = 1.0 / (1.0 + get_url_depth(url.path()) as f64);
+    // Sitemaps default to 0.5 when a URL has no priority.
+    let priority = hint
+      .and_then(|hint| hint.sitemap_priority)
+      .unwrap_or(0.5)
+      .clamp(0.0, 1.0);
+    let specificity = include.map_or(0.0, |index| self.include_specificity[index]);
+
+    self.depth_weight * depth
+      + self.priority_weight * priority
+      + self.pattern_weight * specificity
+      + self.keyword_weight * self.keyword_score(url, hint)
+  }
+
+  /// Keywords count half for appearing in the path or query and half for the anchor text.
+  /// Prefix matches count too, so `doc` matches `docs`.
+  fn keyword_score(&self, url: &Url, hint: Option<&LinkHint>) -> f64 {
+    if self.keywords.is_empty() {
+      return 0.0;
+    }
+
+    let url_tokens: Vec<String> = score_tokens(url.path())
+      .chain(url.query().into_iter().flat_map(score_tokens))
+      .collect();
+    let anchor_tokens: Vec<String> = hint
+      .and_then(|hint| hint.anchor_text.as_deref())
+      .into_iter()
+      .flat_map(score_tokens)
+      .collect();
+    let contains =
+      |tokens: &[String], keyword: &str| tokens.iter().any(|token| token.starts_with(keyword));
+
+    let hits: f64 = self
+      .keywords
+      .iter()
+      .map(|keyword| {
+        0.5 * contains(&url_tokens, keyword) as u8 as f64
+          + 0.5 * contains(&anchor_tokens, keyword) as u8 as f64
+      })
+      .sum();
+    hits / self.keywords.len() as f64
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::crawler::{_filter_links, CrawlFilterOptions, FilterLinksCall, FilterLinksPage};
+  use std::collections::HashMap;
+
+  #[test]
+  fn test_filter_links_scoring() {
+    let hint = |anchor_text: &str, sitemap_priority| LinkHint {
+      anchor_text: Some(anchor_text.to_string()),
+      sitemap_priority,
+      ..Default::default()
+    };
+    let call = |scoring| FilterLinksCall {
+      links: vec![
+        "https://example.com/blog/2021/05/company-news".to_string(),
+        "https://example.com/about".to_string(),
+        "https://example.com/docs/api/pricing".to_string(),
+        "https://example.com/plans".to_string(),
+      ],
+      limit: Some(2),
+      options: CrawlFilterOptions {
+        max_depth: 10,
+        base_url: "https://example.com".to_string(),
+        initial_url: "https://example.com".to_string(),
+        scoring,
+        ..Default::default()
+      },
+      page: Some(FilterLinksPage {
+        link_hints: Some(HashMap::from([
+          (
+            "https://example.com/plans".to_string(),
+            hint("See our pricing", None),
+          ),
+          (
+            "https://example.com/about".to_string(),
+            hint("About us", Some(0.9)),
+          ),
+        ])),
+        ..Default::default()
+      }),
+    };
+
+    // Without scoring the limit keeps the first links in input order.
+    let result = _filter_links(call(None)).unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://example.com/blog/2021/05/company-news",
+        "https://example.com/about",
+      ]
+    );
+    assert!(result.scores.is_none());
+
+    let result = _filter_links(call(Some(LinkScoringOptions {
+      prompt: Some("What are the pricing plans?".to_string()),
+      keyword_weight: Some(4.0),
+      ..Default::default()
+    })))
+    .unwrap();
+    assert_eq!(
+      result.links,
+      vec![
+        "https://example.com/plans",
+        "https://example.com/docs/api/pricing"
+      ]
+    );
+    let scores = result.scores.unwrap();
+    assert_eq!(scores.len(), 2);
+    assert!(scores["https://example.com/plans"] > scores["https://example.com/docs/api/pricing"]);
+
+    // Sitemap priority and depth decide when there is no prompt.
+    let result = _filter_links(call(Some(LinkScoringOptions::default()))).unwrap();
+    assert_eq!(result.links[0], "https://example.com/about");
+
+    // A more specific include pattern ranks its matches higher.
+    let mut data = call(Some(LinkScoringOptions {
+      depth_weight: Some(0.0),
+      priority_weight: Some(0.0),
+      ..Default::default()
+    }));
+    data.options.includes = vec![
+      "^/docs/api/".to_string(),
+      "^/(blog|about|plans)".to_string(),
+    ];
+    let result = _filter_links(data).unwrap();
+    assert_eq!(result.links[0], "https://example.com/docs/api/pricing");
+  }
+}
@@ -0,0 +1,328 @@
+use napi::bindgen_prelude::*;
+use napi_derive::napi;
+use regex::Regex;
+use serde::Deserialize;
+use std::{
+  collections::{HashMap, HashSet},
+  hash::{DefaultHasher, Hash, Hasher},
+  sync::{Arc, LazyLock, Mutex, MutexGuard, PoisonError},
+};
+use tokio::task;
+use url::Url;
+
+use crate::crawler::{url_host, Denial, FilterLinksResult, URL_PARSE_ERROR, URL_TRAP};
+
+#[derive(Deserialize, Default)]
+#[napi(object)]
+pub struct UrlSpaceGuardOptions {
+  /// Distinct URLs allowed per URL template before the rest are denied as `URL_TRAP`.
+  /// Defaults to 100.
+  pub max_urls_per_template: Option<u32>,
+  /// Base for resolving relative links.
+  pub base_url: Option<String>,
+}
+
+const DEFAULT_MAX_URLS_PER_TEMPLATE: u32 = 100;
+
+static UUID_SEGMENT: LazyLock<Regex> = LazyLock::new(|| {
+  Regex::new(r"^(?i)[0-9a-f]{8}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{4}-[0-9a-f]{12}$")
+    .expect("UUID_SEGMENT is a valid static regex pattern")
+});
+static HEX_SEGMENT: LazyLock<Regex> = LazyLock::new(|| {
+  Regex::new(r"^(?i)[0-9a-f]*[0-9][0-9a-f]*$").expect("HEX_SEGMENT is a valid static regex pattern")
+});
+static DIGIT_RUN: LazyLock<Regex> =
+  LazyLock::new(|| Regex::new(r"[0-9]+").expect("DIGIT_RUN is a valid static regex pattern"));
+
+/// Reduces a path segment to its shape: UUIDs, long hex strings and long alphanumeric tokens
+/// become placeholders, matrix parameters are dropped and other digit runs become `{n}`.
+fn segment_template(segment: &str) -> String {
+  let segment = match segment.split_once(';') {
+    Some((segment, _)) => return format!("{};{{params}}", segment_template(segment)),
+    None => segment,
+  };
+
+  if UUID_SEGMENT.is_match(segment) {
+    "{uuid}".to_string()
+  } else if segment.len() >= 16 && HEX_SEGMENT.is_match(segment) {
+    "{hex}".to_string()
+  } else if segment.len() >= 20
+    && segment.chars().all(|c| c.is_ascii_alphanumeric())
+    && segment.chars().any(|c| c.is_ascii_digit())
+  {
+    "{id}".to_string()
+  } else {
+    DIGIT_RUN.replace_all(segment, "{n}").into_owned()
+  }
+}
+
+/// The templated shape of a URL: host, templated path segments and sorted query keys.
+/// `/events/2024/05?view=day&session=1` becomes `host/events/{n}/{n}?session&view`.
+fn url_template(url: &Url) -> String {
+  let mut template = url_host(url).to_string();
+  for segment in url.path_segments().into_iter().flatten() {
+    template.push('/');
+    template.push_str(&segment_template(segment));
+  }
+
+  let mut keys: Vec<String> = url.query_pairs().map(|(key, _)| key.into_owned()).collect();
+  if !keys.is_empty() {
+    keys.sort();
+    keys.dedup();
+    template.push('?');
+    template.push_str(&keys.join("&"));
+  }
+
+  template
+}
+
+/// Tracks how many distinct URLs of each template a crawl has accepted, and denies URLs of
+/// templates that went over budget as `URL_TRAP`. Calendars, faceted search and session IDs
+/// otherwise produce an unbounded number of URLs of one shape.
+#[napi]
+pub struct UrlSpaceGuard {
+  base_url: Option<Url>,
+  budget: Arc<Mutex<UrlTemplateBudget>>,
+}
+
+#[napi]
+impl UrlSpaceGuard {
+  #[napi(constructor)]
+  pub fn new(options: Option<UrlSpaceGuardOptions>) -> Result<Self> {
+    let options = options.unwrap_or_default();
+    let base_url = options
+      .base_url
+      .as_deref()
+      .map(Url::parse)
+      .transpose()
+      .map_err(|e| {
+        Error::new(
+          Status::GenericFailure,
+          format!("URL space guard error: Base URL parse error: {e}"),
+        )
+      })?;
+
+    Ok(Self {
+      base_url,
+      budget: Arc::new(Mutex::new(UrlTemplateBudget {
+        max_urls_per_template: options
+          .max_urls_per_template
+          .unwrap_or(DEFAULT_MAX_URLS_PER_TEMPLATE),
+        template_counts: HashMap::new(),
+        seen: HashSet::new(),
+      })),
+    })
+  }
+
+  /// The template a link is counted under, or `null` if it doesn't resolve.
+  #[napi]
+  pub fn template(&self, link: String) -> Option<String> {
+    resolve_guarded_link(self.base_url.as_ref(), &link).map(|url| url_template(&url))
+  }
+
+  /// Records `links` against their templates and denies those over budget as `URL_TRAP`.
+  /// `limit` is applied after trap links are removed, so a trap can't use it up.
+  #[napi]
+  pub async fn filter_links(
+    &self,
+    links: Vec<String>,
+    limit: Option<i64>,
+  ) -> Result<FilterLinksResult> {
+    let base_url = self.base_url.clone();
+    let budget = Arc::clone(&self.budget);
+    task::spawn_blocking(move || filter_url_space(base_url.as_ref(), &budget, links, limit))
+      .await
+      .map_err(|e| {
+        napi::Error::new(
+          napi::Status::GenericFailure,
+          format!("filter_links join error: {e}"),
+        )
+      })
+  }
+
+  /// Number of distinct URLs accepted so far, per template.
+  #[napi]
+  pub fn template_counts(&self) -> HashMap<String, u32> {
+    self.lock().template_counts.clone()
+  }
+
+  /// Only held while counts are read or updated, so this doesn't wait on links being
+  /// resolved and templated by a pending `filter_links`.
+  fn lock(&self) -> MutexGuard<'_, UrlTemplateBudget> {
+    self.budget.lock().unwrap_or_else(PoisonError::into_inner)
+  }
+}
+
+fn resolve_guarded_link(base_url: Option<&Url>, link: &str) -> Option<Url> {
+  match base_url {
+    Some(base_url) => base_url.join(link).ok(),
+    None => Url::parse(link).ok(),
+  }
+}
+
+/// Resolves and templates `links` without taking the lock, then holds it only to count them.
+fn filter_url_space(
+  base_url: Option<&Url>,
+  budget: &Mutex<UrlTemplateBudget>,
+  links: Vec<String>,
+  limit: Option<i64>,
+) -> FilterLinksResult {
+  let limit = limit.map_or(usize::MAX, |x| x.max(0) as usize);
+  let links: Vec<_> = links
+    .into_iter()
+    .map(|link| {
+      let url = resolve_guarded_link(base_url, &link).map(|url| {
+        let mut hasher = DefaultHasher::new();
+        url.as_str().hash(&mut hasher);
+        (url_template(&url), hasher.finish(), url)
+      });
+      (link, url)
+    })
+    .collect();
+
+  let mut result_links = Vec::new();
+  let mut denial_reasons = HashMap::new();
+  let mut denials = HashMap::new();
+  let mut budget = budget.lock().unwrap_or_else(PoisonError::into_inner);
+  for (link, url) in links {
+    if result_links.len() >= limit {
+      break;
+    }
+
+    let Some((template, fingerprint, url)) = url else {
+      denial_reasons.insert(link.clone(), URL_PARSE_ERROR.to_string());
+      denials.insert(link, Denial::new(URL_PARSE_ERROR).into_link_denial(None));
+      continue;
+    };
+
+    if budget.record(&template, fingerprint) {
+      result_links.push(link);
+    } else {
+      let denial = Denial {
+        pattern: Some(template),
+        ..Denial::new(URL_TRAP)
+      };
+      denial_reasons.insert(link.clone(), URL_TRAP.to_string());
+      denials.insert(link, denial.into_link_denial(Some(url.into())));
+    }
+  }
+
+  FilterLinksResult {
+    links: result_links,
+    denial_reasons,
+    denials,
+    robots_user_agent: None,
+    link_depth: None,
+    scores: None,
+  }
+}
+
+/// The state behind `UrlSpaceGuard`. `seen` keeps a 64-bit fingerprint, not the URL, of every
+/// accepted URL; each template accepts at most `max_urls_per_template` of them, so it grows with
+/// the number of distinct templates a crawl runs into.
+struct UrlTemplateBudget {
+  max_urls_per_template: u32,
+  template_counts: HashMap<String, u32>,
+  seen: HashSet<u64>,
+}
+
+impl UrlTemplateBudget {
+  /// Counts the URL with `fingerprint` against `template` unless it was seen before. Returns
+  /// whether it fits in the budget.
+  fn record(&mut self, template: &str, fingerprint: u64) -> bool {
+    if self.seen.contains(&fingerprint) {
+      return true;
+    }
+
+    let count = self
+      .template_counts
+      .entry(template.to_string())
+      .or_insert(0);
+    if *count >= self.max_urls_per_template {
+      return false;
+    }
+
+    *count += 1;
+    self.seen.insert(fingerprint);
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_url_template() {
+    let template = |url: &str| url_template(&Url::parse(url).unwrap());
+
+    assert_eq!(
+      template("https://example.com/events/2024/05/12?view=day&session=1&view=week"),
+      "example.com/events/{n}/{n}/{n}?session&view"
+    );
+    assert_eq!(
+      template("https://example.com/item/3f2b8c1e-1a2b-4c3d-9e8f-0a1b2c3d4e5f"),
+      "example.com/item/{uuid}"
+    );
+    assert_eq!(
+      template("https://example.com/s/0123456789abcdef0123/page-2"),
+      "example.com/s/{hex}/page-{n}"
+    );
+    assert_eq!(
+      template("https://example.com/cart;jsessionid=ABC123"),
+      "example.com/cart;{params}"
+    );
+  }
+
+  #[test]
+  fn test_url_space_guard_denies_traps() {
+    let guard = UrlSpaceGuard::new(Some(UrlSpaceGuardOptions {
+      max_urls_per_template: Some(2),
+      base_url: Some("https://example.com".to_string()),
+    }))
+    .unwrap();
+
+    let filter_links = |links: Vec<String>, limit| {
+      filter_url_space(guard.base_url.as_ref(), &guard.budget, links, limit)
+    };
+    let result = filter_links(
+      vec![
+        "/calendar/2024/01".to_string(),
+        "/calendar/2024/02".to_string(),
+        "/calendar/2024/01".to_string(),
+        "/calendar/2024/03".to_string(),
+        "/about".to_string(),
+      ],
+      None,
+    );
+    assert_eq!(
+      result.links,
+      vec![
+        "/calendar/2024/01",
+        "/calendar/2024/02",
+        "/calendar/2024/01",
+        "/about"
+      ]
+    );
+    assert_eq!(result.denial_reasons["/calendar/2024/03"], "URL_TRAP");
+
+    // Counts persist across calls, and trap links don't use up the limit.
+    let result = filter_links(
+      vec![
+        "/calendar/2025/01".to_string(),
+        "/calendar/2025/02".to_string(),
+        "/team".to_string(),
+        "/pricing".to_string(),
+      ],
+      Some(1),
+    );
+    assert_eq!(result.links, vec!["/team"]);
+    assert_eq!(result.denial_reasons.len(), 2);
+
+    assert_eq!(guard.template_counts()["example.com/calendar/{n}/{n}"], 2);
+    assert_eq!(
+      guard.template("/calendar/2030/12".to_string()).as_deref(),
+      Some("example.com/calendar/{n}/{n}")
+    );
+  }
+}
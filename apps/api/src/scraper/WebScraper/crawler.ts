@@ -179,17 +179,19 @@ export class WebCrawler {
       const res = await filterLinks({
         links: sitemapLinks,
         limit: isFinite(limit) ? limit : undefined,
-        maxDepth: maxDepth,
-        baseUrl: this.baseUrl,
-        initialUrl: this.initialUrl,
-        regexOnFullUrl: this.regexOnFullURL,
-        excludes: this.excludes,
-        includes: this.includes,
-        allowBackwardCrawling: this.allowBackwardCrawling,
-        ignoreRobotsTxt: this.ignoreRobotsTxt || skipRobots,
-        robotsTxt: this.robotsTxt,
-        allowExternalContentLinks: this.allowExternalContentLinks,
-        allowSubdomains: this.allowSubdomains,
+        options: {
+          maxDepth: maxDepth,
+          baseUrl: this.baseUrl,
+          initialUrl: this.initialUrl,
+          regexOnFullUrl: this.regexOnFullURL,
+          excludes: this.excludes,
+          includes: this.includes,
+          allowBackwardCrawling: this.allowBackwardCrawling,
+          ignoreRobotsTxt: this.ignoreRobotsTxt || skipRobots,
+          robotsTxt: this.robotsTxt,
+          allowExternalContentLinks: this.allowExternalContentLinks,
+          allowSubdomains: this.allowSubdomains,
+        },
       });
 
       const fancyDenialReasons = new Map<string, string>();
@@ -651,12 +653,19 @@ export class WebCrawler {
     return await filterUrl({
       href: href,
       url: url,
-      baseUrl: this.baseUrl,
-      excludes: this.excludes,
-      ignoreRobotsTxt: this.ignoreRobotsTxt,
-      robotsTxt: this.robotsTxt,
-      allowExternalContentLinks: this.allowExternalContentLinks,
-      allowSubdomains: this.allowSubdomains,
+      options: {
+        maxDepth: this.maxCrawledDepth,
+        baseUrl: this.baseUrl,
+        initialUrl: this.initialUrl,
+        regexOnFullUrl: this.regexOnFullURL,
+        excludes: this.excludes,
+        includes: this.includes,
+        allowBackwardCrawling: this.allowBackwardCrawling,
+        ignoreRobotsTxt: this.ignoreRobotsTxt,
+        robotsTxt: this.robotsTxt,
+        allowExternalContentLinks: this.allowExternalContentLinks,
+        allowSubdomains: this.allowSubdomains,
+      },
     });
   }
 